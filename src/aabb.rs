@@ -0,0 +1,173 @@
+use crate::{
+    ray::Ray,
+    vec3::{Point, Vec3},
+};
+
+/// Axis-aligned bounding box, used by the BVH to skip whole groups of objects.
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Aabb {
+    // the two points can be any opposite corners of the box
+    pub fn new(a: Point, b: Point) -> Aabb {
+        Aabb {
+            min: Point::new(a.i.min(b.i), a.j.min(b.j), a.k.min(b.k)),
+            max: Point::new(a.i.max(b.i), a.j.max(b.j), a.k.max(b.k)),
+        }
+    }
+
+    // a box that contains nothing, neutral element of `surrounding`
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    // a box that contains everything, for unbounded shapes
+    pub fn infinite() -> Aabb {
+        Aabb {
+            min: Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            max: Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        }
+    }
+
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Point::new(
+                self.min.i.min(other.min.i),
+                self.min.j.min(other.min.j),
+                self.min.k.min(other.min.k),
+            ),
+            max: Point::new(
+                self.max.i.max(other.max.i),
+                self.max.j.max(other.max.j),
+                self.max.k.max(other.max.k),
+            ),
+        }
+    }
+
+    // widen the sides thinner than `delta`, flat shapes would give a degenerate box otherwise
+    pub fn padded(&self, delta: f64) -> Aabb {
+        let mut min = self.min;
+        let mut max = self.max;
+        if max.i - min.i < delta {
+            min.i -= delta / 2.0;
+            max.i += delta / 2.0;
+        }
+        if max.j - min.j < delta {
+            min.j -= delta / 2.0;
+            max.j += delta / 2.0;
+        }
+        if max.k - min.k < delta {
+            min.k -= delta / 2.0;
+            max.k += delta / 2.0;
+        }
+        Aabb { min, max }
+    }
+
+    pub fn centroid(&self) -> Point {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    // index of the axis along which the box is the widest
+    pub fn longest_axis(&self) -> usize {
+        let extent = self.extent();
+        if extent.i > extent.j && extent.i > extent.k {
+            0
+        } else if extent.j > extent.k {
+            1
+        } else {
+            2
+        }
+    }
+
+    // slab test, only tells whether the ray enters the box within [ray_tmin, ray_tmax]
+    pub fn hit(&self, ray: &Ray, mut ray_tmin: f64, mut ray_tmax: f64) -> bool {
+        let origin = ray.origin();
+        let dir = ray.dir();
+
+        for axis in 0..3 {
+            let inv_d = 1.0 / dir[axis];
+            let mut t0 = (self.min[axis] - origin[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            ray_tmin = if t0 > ray_tmin { t0 } else { ray_tmin };
+            ray_tmax = if t1 < ray_tmax { t1 } else { ray_tmax };
+            if ray_tmax < ray_tmin {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_orders_corners() {
+        let b = Aabb::new(Point::new(1.0, -1.0, 3.0), Point::new(-1.0, 2.0, 0.0));
+
+        assert!(b.min.i == -1.0 && b.min.j == -1.0 && b.min.k == 0.0);
+        assert!(b.max.i == 1.0 && b.max.j == 2.0 && b.max.k == 3.0);
+    }
+
+    #[test]
+    fn test_surrounding() {
+        let b1 = Aabb::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 1.0, 1.0));
+        let b2 = Aabb::new(Point::new(-1.0, 0.5, 0.5), Point::new(0.5, 0.5, 4.0));
+        let b3 = b1.surrounding(&b2);
+
+        assert!(b3.min.i == -1.0 && b3.min.j == 0.0 && b3.min.k == 0.0);
+        assert!(b3.max.i == 1.0 && b3.max.j == 1.0 && b3.max.k == 4.0);
+    }
+
+    #[test]
+    fn test_empty_is_neutral() {
+        let b1 = Aabb::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 1.0, 1.0));
+        let b2 = Aabb::empty().surrounding(&b1);
+
+        assert!(b2.min.i == 0.0 && b2.max.k == 1.0);
+    }
+
+    #[test]
+    fn test_padded() {
+        let b = Aabb::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.0, 1.0)).padded(0.5);
+
+        assert!(b.min.j == -0.25 && b.max.j == 0.25);
+        assert!(b.min.i == 0.0 && b.max.i == 1.0);
+    }
+
+    #[test]
+    fn test_longest_axis() {
+        let b = Aabb::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 3.0, 2.0));
+
+        assert!(b.longest_axis() == 1);
+    }
+
+    #[test]
+    fn test_hit() {
+        let b = Aabb::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0));
+        let towards = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let away = Ray::new(Point::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, -1.0));
+        let beside = Ray::new(Point::new(2.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        assert!(b.hit(&towards, 0.0, f64::INFINITY));
+        assert!(!b.hit(&towards, 0.0, 3.0));
+        assert!(!b.hit(&away, 0.0, f64::INFINITY));
+        assert!(!b.hit(&beside, 0.0, f64::INFINITY));
+    }
+}
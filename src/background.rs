@@ -0,0 +1,105 @@
+use crate::{material::Rgb, random::RandomGenerator, ray::Ray, vec3::Vec3};
+
+/// What a ray sees when it leaves the scene without hitting anything. It's also the only light
+/// of scenes without emissive materials.
+pub trait Background: Send + Sync {
+    fn color(&self, ray: &Ray) -> Rgb;
+
+    /// Picks a direction towards the background with its density per steradian, for
+    /// backgrounds bright enough in places to be worth aiming at. `None` if not supported.
+    fn sample(&self, _random_generator: &mut RandomGenerator) -> Option<(Vec3, f64)> {
+        None
+    }
+
+    /// Density of `sample` returning `direction`.
+    fn pdf(&self, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    /// Whether `sample` is supported, the integrators only send light samples toward the
+    /// backgrounds that say so.
+    fn is_sampled(&self) -> bool {
+        false
+    }
+}
+
+/// A solid color, `Rgb::new(0.0, 0.0, 0.0)` makes closed interiors lit only by their lights.
+impl Background for Rgb {
+    fn color(&self, _: &Ray) -> Rgb {
+        *self
+    }
+}
+
+/// Any closure of the ray direction works as a custom background.
+impl<F> Background for F
+where
+    F: Fn(&Ray) -> Rgb + Send + Sync,
+{
+    fn color(&self, ray: &Ray) -> Rgb {
+        self(ray)
+    }
+}
+
+/// Linear blend from `bottom` to `top` along the `up` direction.
+pub struct Gradient {
+    pub bottom: Rgb,
+    pub top: Rgb,
+    pub up: Vec3,
+}
+
+impl Gradient {
+    // the blue-white daylight of the book
+    pub fn sky() -> Gradient {
+        Gradient {
+            bottom: Rgb::white(),
+            top: Rgb::new(0.5, 0.7, 1.0),
+            up: Vec3::new(0.0, 1.0, 0.0),
+        }
+    }
+}
+
+impl Background for Gradient {
+    fn color(&self, ray: &Ray) -> Rgb {
+        let unit_dir = ray.dir().unit_vector();
+        let a = 0.5 * (unit_dir.dot(self.up.unit_vector()) + 1.0);
+        (1.0 - a) * self.bottom + a * self.top
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Point;
+
+    fn ray(direction: Vec3) -> Ray {
+        Ray::new(Point::new(0.0, 0.0, 0.0), direction)
+    }
+
+    #[test]
+    fn test_solid() {
+        let c1 = Rgb::new(0.1, 0.2, 0.3).color(&ray(Vec3::new(0.0, 1.0, 0.0)));
+        assert!(!Rgb::new(0.1, 0.2, 0.3).is_sampled());
+
+        assert!(c1.r == 0.1 && c1.g == 0.2 && c1.b == 0.3);
+    }
+
+    #[test]
+    fn test_sky() {
+        let sky = Gradient::sky();
+        let up = sky.color(&ray(Vec3::new(0.0, 2.0, 0.0)));
+        let down = sky.color(&ray(Vec3::new(0.0, -2.0, 0.0)));
+        let side = sky.color(&ray(Vec3::new(1.0, 0.0, 0.0)));
+
+        assert!(up.r == 0.5 && up.g == 0.7 && up.b == 1.0);
+        assert!(down.r == 1.0 && down.g == 1.0 && down.b == 1.0);
+        assert!(side.r == 0.75 && side.b == 1.0);
+    }
+
+    #[test]
+    fn test_closure() {
+        let background = |ray: &Ray| Rgb::new(ray.dir().i, 0.0, 0.0);
+        let c1 = background.color(&ray(Vec3::new(0.5, 0.0, 0.0)));
+
+        assert!(c1.r == 0.5);
+    }
+}
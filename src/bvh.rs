@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable, HittableList},
+    ray::Ray,
+};
+
+/// Bounding volume hierarchy over a list of objects. A ray only visits the subtrees whose
+/// bounding box it enters, so the cost per ray is roughly logarithmic in the object count.
+pub enum BvhNode {
+    Leaf(Arc<dyn Hittable>),
+    Branch {
+        left: Box<BvhNode>,
+        right: Box<BvhNode>,
+        bbox: Aabb,
+    },
+}
+
+impl BvhNode {
+    pub fn new(mut objects: HittableList) -> BvhNode {
+        match objects.len() {
+            0 => BvhNode::Leaf(Arc::new(HittableList::new())),
+            1 => BvhNode::Leaf(objects.pop().unwrap()),
+            _ => {
+                // split at the median along the axis where the centroids spread the most
+                let centroid_bounds = objects.iter().fold(Aabb::empty(), |bounds, object| {
+                    let centroid = object.bounding_box().centroid();
+                    bounds.surrounding(&Aabb::new(centroid, centroid))
+                });
+                let axis = centroid_bounds.longest_axis();
+                objects.sort_by(|a, b| {
+                    let a = a.bounding_box().centroid()[axis];
+                    let b = b.bounding_box().centroid()[axis];
+                    a.total_cmp(&b)
+                });
+
+                let right = objects.split_off(objects.len() / 2);
+                let left = Box::new(BvhNode::new(objects));
+                let right = Box::new(BvhNode::new(right));
+                let bbox = left.bounding_box().surrounding(&right.bounding_box());
+
+                BvhNode::Branch { left, right, bbox }
+            }
+        }
+    }
+}
+
+impl From<HittableList> for BvhNode {
+    fn from(objects: HittableList) -> BvhNode {
+        BvhNode::new(objects)
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        match self {
+            BvhNode::Leaf(object) => object.hit(ray, ray_tmin, ray_tmax),
+            BvhNode::Branch { left, right, bbox } => {
+                if !bbox.hit(ray, ray_tmin, ray_tmax) {
+                    return None;
+                }
+
+                let hit_left = left.hit(ray, ray_tmin, ray_tmax);
+                let closest_so_far = hit_left.as_ref().map_or(ray_tmax, |rec| rec.t);
+                right.hit(ray, ray_tmin, closest_so_far).or(hit_left)
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match self {
+            BvhNode::Leaf(object) => object.bounding_box(),
+            BvhNode::Branch { bbox, .. } => *bbox,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::{Lambertian, Rgb},
+        shape::{Plane, Sphere},
+        vec3::{Point, Vec3},
+    };
+
+    fn spheres() -> HittableList {
+        let material = Arc::new(Lambertian::new(Rgb::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::new();
+        for a in -5..5 {
+            for b in -5..5 {
+                list.push(Arc::new(Sphere::new(
+                    Point::new(a as f64, 0.3 * b as f64, b as f64),
+                    0.3,
+                    material.clone(),
+                )));
+            }
+        }
+        list
+    }
+
+    #[test]
+    fn test_same_hits_as_list() {
+        let list = spheres();
+        let bvh = BvhNode::new(spheres());
+
+        for x in -20..20 {
+            for y in -20..20 {
+                let ray = Ray::new(
+                    Point::new(0.0, 3.0, -12.0),
+                    Vec3::new(x as f64 * 0.02, y as f64 * 0.02 - 0.2, 1.0),
+                );
+                let expected = list.hit(&ray, 0.001, f64::INFINITY).map(|rec| rec.t);
+                let actual = bvh.hit(&ray, 0.001, f64::INFINITY).map(|rec| rec.t);
+
+                assert!(expected == actual);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unbounded_objects() {
+        // infinite boxes have no centroid, the split must still keep every object
+        let mut list = spheres();
+        let material = Arc::new(Lambertian::new(Rgb::new(0.5, 0.5, 0.5)));
+        list.push(Arc::new(Plane::new(
+            Point::new(0.0, -2.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            material,
+        )));
+        let bvh = BvhNode::new(list.clone());
+
+        for x in -20..20 {
+            let ray = Ray::new(
+                Point::new(0.0, 3.0, -12.0),
+                Vec3::new(x as f64 * 0.05, -0.4, 1.0),
+            );
+            let expected = list.hit(&ray, 0.001, f64::INFINITY).map(|rec| rec.t);
+            let actual = bvh.hit(&ray, 0.001, f64::INFINITY).map(|rec| rec.t);
+
+            assert!(expected.is_some() && expected == actual);
+        }
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let bbox = BvhNode::new(spheres()).bounding_box();
+
+        assert!(bbox.min.i == -5.3 && bbox.max.i == 4.3);
+        assert!(bbox.min.k == -5.3 && bbox.max.k == 4.3);
+    }
+
+    #[test]
+    fn test_empty() {
+        let bvh = BvhNode::new(HittableList::new());
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+
+        assert!(bvh.hit(&ray, 0.001, f64::INFINITY).is_none());
+    }
+}
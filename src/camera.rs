@@ -1,21 +1,44 @@
 #![allow(dead_code)]
 
 use crate::{
+    background::{Background, Gradient},
+    framebuffer::Framebuffer,
     hittable::{Hittable, HittableList},
+    integrator::{Integrator, PathTracer, SceneView},
     material::Rgb,
+    random::RandomGenerator,
     ray::Ray,
+    tonemap::ToneMapping,
     vec3::{Point, Vec3},
 };
-use image::EncodableLayout;
+use image::ImageResult;
 use indicatif::ProgressBar;
-use rand::{rngs::ThreadRng, Rng};
-use std::f64::INFINITY;
+use rand::Rng;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+// edge length of the square tiles handed out to the render threads
+const TILE_SIZE: usize = 32;
 
+#[derive(Clone, Copy, Debug)]
+struct Tile {
+    x0: usize,
+    y0: usize,
+    x1: usize, // exclusive
+    y1: usize, // exclusive
+}
+
+#[derive(Clone)]
 pub struct CameraConfig<'a> {
     pub aspect_ratio: f64,
     pub width: usize,
     pub samples_per_pixel: usize,
-    pub max_depth: usize,
+    pub max_depth: usize, // hard cap on the bounces, paths usually end well before
     pub float_correction: f64,
     pub vfov: f64, // vertical field of view, angle
     pub look_from: Point,
@@ -23,7 +46,18 @@ pub struct CameraConfig<'a> {
     pub camera_vup: Vec3,
     pub defocus_angle: f64,
     pub focus_dist: f64,
+    // the rays' times are spread over [shutter_open, shutter_close], moving objects are
+    // blurred along the way they go during that interval
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    pub background: Arc<dyn Background>, // seen by the rays that miss everything
+    pub integrator: Arc<dyn Integrator>, // turns the camera rays into colors
     pub save_path: &'a str,
+    pub hdr_save_path: Option<&'a str>, // extra linear .exr or .hdr output
+    pub tone_mapping: ToneMapping,      // only used by the 8-bit outputs
+    pub threads: usize,                 // render threads, 0 means one per available core
+    pub seed: u64,                      // same seed and scene give the same image
+    pub quiet: bool,                    // no progress bar nor messages
 }
 
 impl<'a> Default for CameraConfig<'a> {
@@ -32,7 +66,7 @@ impl<'a> Default for CameraConfig<'a> {
             aspect_ratio: 16.0 / 9.0,
             width: 800,
             samples_per_pixel: 10,
-            max_depth: 20,
+            max_depth: 64,
             float_correction: 0.0001,
             vfov: 90.0,
             look_from: Point::new(0.0, 0.0, 0.0),
@@ -40,7 +74,16 @@ impl<'a> Default for CameraConfig<'a> {
             camera_vup: Vec3::new(0.0, 1.0, 0.0),
             defocus_angle: 1.0,
             focus_dist: 10.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            background: Arc::new(Gradient::sky()),
+            integrator: Arc::new(PathTracer::default()),
             save_path: "/tmp/pic.png",
+            hdr_save_path: None,
+            tone_mapping: ToneMapping::default(),
+            threads: 0,
+            seed: 0,
+            quiet: false,
         }
     }
 }
@@ -58,7 +101,7 @@ pub struct Camera<'a> {
     viewport_upperleft: Point,
     pixel00loc: Point,
     indicator_bar: ProgressBar,
-    cache: Vec<u8>,
+    framebuffer: Framebuffer,
     samples_per_pixel: usize,
     max_depth: usize,
     float_correction: f64,
@@ -73,7 +116,17 @@ pub struct Camera<'a> {
     focus_dist: f64,
     defocus_u: Vec3,
     defocus_v: Vec3,
+    shutter_open: f64,
+    shutter_close: f64,
+    background: Arc<dyn Background>,
+    sample_background: bool, // whether `background` supports sampling
+    integrator: Arc<dyn Integrator>,
     save_path: &'a str,
+    hdr_save_path: Option<&'a str>,
+    tone_mapping: ToneMapping,
+    threads: usize,
+    random_generator: RandomGenerator,
+    quiet: bool,
 }
 
 impl<'a> Camera<'a> {
@@ -114,9 +167,18 @@ impl<'a> Camera<'a> {
         let pixel00loc = viewport_upperleft + 0.5 * (delta_u + delta_v);
 
         // misc
-        let indicator_bar = ProgressBar::new(height as u64);
-        let mut cache: Vec<u8> = vec![];
-        cache.reserve(config.width * height * 3);
+        let tiles_count = config.width.div_ceil(TILE_SIZE) * height.div_ceil(TILE_SIZE);
+        let indicator_bar = if config.quiet {
+            ProgressBar::hidden()
+        } else {
+            ProgressBar::new(tiles_count as u64)
+        };
+        let framebuffer = Framebuffer::new(config.width, height);
+        let threads = match config.threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        let sample_background = config.background.is_sampled();
 
         Camera {
             aspect_ratio: config.aspect_ratio,
@@ -131,7 +193,7 @@ impl<'a> Camera<'a> {
             viewport_upperleft,
             pixel00loc,
             indicator_bar,
-            cache,
+            framebuffer,
             samples_per_pixel: config.samples_per_pixel,
             max_depth: config.max_depth,
             float_correction: config.float_correction,
@@ -146,98 +208,220 @@ impl<'a> Camera<'a> {
             focus_dist: config.focus_dist,
             defocus_u,
             defocus_v,
+            shutter_open: config.shutter_open,
+            shutter_close: config.shutter_close,
+            background: config.background,
+            sample_background,
+            integrator: config.integrator,
             save_path: config.save_path,
+            hdr_save_path: config.hdr_save_path,
+            tone_mapping: config.tone_mapping,
+            threads,
+            random_generator: RandomGenerator::new(config.seed),
+            quiet: config.quiet,
         }
     }
 
-    pub fn render(&mut self, world: &HittableList, random_generator: &mut ThreadRng) {
-        for i in 0..self.height {
-            for j in 0..self.width {
-                let pixel_center =
-                    self.pixel00loc + j as f64 * self.delta_u + i as f64 * self.delta_v;
+    /// Renders `world` and saves the image. `lights` are the emissive shapes worth sending
+    /// rays toward, they should be in `world` too.
+    pub fn render(&mut self, world: &dyn Hittable, lights: &HittableList) -> ImageResult<()> {
+        let tiles = self.tiles();
+        let next_tile = AtomicUsize::new(0);
 
-                let mut color = Rgb::new(0.0, 0.0, 0.0);
-                for _ in 0..self.samples_per_pixel {
-                    color = color
-                        + self.ray_color(
-                            &self.get_ray(pixel_center, random_generator),
-                            &world,
-                            random_generator,
-                            self.max_depth,
-                        );
+        let camera = &*self;
+        let scene = SceneView {
+            world,
+            lights,
+            background: camera.background.as_ref(),
+            sample_background: camera.sample_background,
+            float_correction: camera.float_correction,
+            max_depth: camera.max_depth,
+        };
+        let rendered: Vec<(Tile, Vec<Rgb>)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..camera.threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = vec![];
+                        while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed))
+                        {
+                            let colors = camera.render_tile(tile, &scene);
+                            done.push((*tile, colors));
+                            camera.indicator_bar.inc(1);
+                        }
+                        done
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        for (tile, colors) in rendered {
+            let mut colors = colors.into_iter();
+            for i in tile.y0..tile.y1 {
+                for j in tile.x0..tile.x1 {
+                    self.write_color(i, j, colors.next().unwrap());
                 }
-                self.write_color(color);
             }
-            self.indicator_bar.inc(1);
         }
 
         self.indicator_bar.finish();
 
-        println!("Saving file...");
-        image::save_buffer(
-            self.save_path,
-            self.cache.as_bytes(),
-            self.width as u32,
-            self.height as u32,
-            image::ColorType::Rgb8,
-        )
-        .unwrap();
-        println!("Done");
+        if !self.quiet {
+            println!("Saving file...");
+        }
+        self.framebuffer.save(self.save_path, &self.tone_mapping)?;
+        if let Some(hdr_save_path) = self.hdr_save_path {
+            self.framebuffer.save(hdr_save_path, &self.tone_mapping)?;
+        }
+        if !self.quiet {
+            println!("Done");
+        }
+        Ok(())
     }
 
-    fn ray_color(
-        &self,
-        r: &Ray,
-        world: &HittableList,
-        random_generator: &mut ThreadRng,
-        depth: usize,
-    ) -> Rgb {
-        if depth == 0 {
-            return Rgb::new(0.0, 0.0, 0.0);
+    fn tiles(&self) -> Vec<Tile> {
+        let mut tiles = vec![];
+        for y0 in (0..self.height).step_by(TILE_SIZE) {
+            for x0 in (0..self.width).step_by(TILE_SIZE) {
+                tiles.push(Tile {
+                    x0,
+                    y0,
+                    x1: (x0 + TILE_SIZE).min(self.width),
+                    y1: (y0 + TILE_SIZE).min(self.height),
+                });
+            }
         }
+        tiles
+    }
 
-        match world.hit(r, self.float_correction, INFINITY) {
-            Some(hit_record) => {
-                let (ray, color) = hit_record
-                    .material
-                    .scatter(r, &hit_record, random_generator);
-                Rgb::from(self.ray_color(&ray, world, random_generator, depth - 1)) * color
-            }
-            _ => {
-                let unit_dir = r.dir().unit_vector();
-                let a = 0.5 * (unit_dir.j + 1.0);
-                (1.0 - a) * Rgb::white() + a * Rgb::new(0.5, 0.7, 1.0)
+    // returns the summed samples of every pixel in the tile, row by row
+    fn render_tile(&self, tile: &Tile, scene: &SceneView) -> Vec<Rgb> {
+        let mut colors = Vec::with_capacity((tile.x1 - tile.x0) * (tile.y1 - tile.y0));
+        for i in tile.y0..tile.y1 {
+            for j in tile.x0..tile.x1 {
+                let pixel_center =
+                    self.pixel00loc + j as f64 * self.delta_u + i as f64 * self.delta_v;
+                // one stream per pixel, so the image doesn't depend on the thread scheduling
+                let random_generator =
+                    &mut self.random_generator.split((i * self.width + j) as u64);
+
+                let mut color = Rgb::new(0.0, 0.0, 0.0);
+                for _ in 0..self.samples_per_pixel {
+                    let ray = self.get_ray(pixel_center, random_generator);
+                    color = color + self.integrator.radiance(&ray, scene, random_generator);
+                }
+                colors.push(color);
             }
         }
+        colors
+    }
+
+    fn write_color(&mut self, i: usize, j: usize, color: Rgb) {
+        self.framebuffer.set(i, j, color / self.samples_per_pixel);
     }
 
-    fn write_color(&mut self, color: Rgb) {
-        let color_desaturated = (color / self.samples_per_pixel).to_gamma() * 255.0;
-        self.cache.push(color_desaturated.r as u8);
-        self.cache.push(color_desaturated.g as u8);
-        self.cache.push(color_desaturated.b as u8);
+    // the averaged linear colors of the last render
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
     }
 
-    fn random_sample_square(&self, random_generator: &mut ThreadRng) -> Point {
+    fn random_sample_square(&self, random_generator: &mut RandomGenerator) -> Point {
         random_generator.gen_range(-0.5..0.5) * self.delta_u
             + random_generator.gen_range(-0.5..0.5) * self.delta_v
     }
 
-    fn get_ray(&self, pixel_center: Point, random_generator: &mut ThreadRng) -> Ray {
+    fn get_ray(&self, pixel_center: Point, random_generator: &mut RandomGenerator) -> Ray {
         let random_point = self.random_sample_square(random_generator) + pixel_center;
         let ray_origin = if self.defocus_angle <= 0.0 {
             self.look_from
         } else {
             self.defocus_disk_sample(random_generator)
         };
+        // without a shutter interval, don't draw a time so the random sequence stays the same
+        let time = if self.shutter_close > self.shutter_open {
+            random_generator.gen_range(self.shutter_open..self.shutter_close)
+        } else {
+            self.shutter_open
+        };
         Ray {
             origin: ray_origin,
             direction: random_point - ray_origin,
+            time,
         }
     }
 
-    fn defocus_disk_sample(&self, random_generator: &mut ThreadRng) -> Point {
+    fn defocus_disk_sample(&self, random_generator: &mut RandomGenerator) -> Point {
         let p = Vec3::random_in_unit_circle(random_generator);
         self.look_from + p.i * self.defocus_u + p.j * self.defocus_v
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::{DiffuseLight, Lambertian},
+        shape::Sphere,
+    };
+
+    fn render(threads: usize, seed: u64) -> Framebuffer {
+        let dir = std::env::temp_dir().join("rtoneweekend_camera_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("render_{}_{}.png", threads, seed));
+        let lamp = Arc::new(Sphere::new(
+            Point::new(0.0, 2.0, -2.0),
+            0.5,
+            Arc::new(DiffuseLight {
+                emit: Rgb::new(4.0, 4.0, 4.0),
+            }),
+        ));
+        let world: HittableList = vec![
+            Arc::new(Sphere::new(
+                Point::new(0.0, 0.0, -2.0),
+                1.0,
+                Arc::new(Lambertian::new(Rgb::new(0.5, 0.3, 0.2))),
+            )),
+            lamp.clone(),
+        ];
+        let lights: HittableList = vec![lamp];
+
+        // wider than a tile, so the threads share the image
+        let mut camera = Camera::create(CameraConfig {
+            width: 70,
+            samples_per_pixel: 4,
+            save_path: path.to_str().unwrap(),
+            threads,
+            seed,
+            quiet: true,
+            ..CameraConfig::default()
+        });
+        camera.render(&world, &lights).unwrap();
+        camera.framebuffer
+    }
+
+    fn same(a: &Framebuffer, b: &Framebuffer) -> bool {
+        (0..a.height()).all(|i| {
+            (0..a.width()).all(|j| {
+                let (a, b) = (a.get(i, j), b.get(i, j));
+                a.r.to_bits() == b.r.to_bits()
+                    && a.g.to_bits() == b.g.to_bits()
+                    && a.b.to_bits() == b.b.to_bits()
+            })
+        })
+    }
+
+    // the regression tests need the same image for the same seed, whatever the scheduling
+    #[test]
+    fn test_reproducible() {
+        let single = render(1, 7);
+        let parallel = render(4, 7);
+        let reseeded = render(4, 8);
+
+        assert!(same(&single, &parallel));
+        assert!(!same(&single, &reseeded));
+    }
+}
@@ -0,0 +1,160 @@
+/// Piecewise-constant 1D distribution over [0, 1), sampled by inverting its CDF.
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    // `func` holds the non-negative weights of `func.len()` equal buckets
+    pub fn new(func: Vec<f64>) -> Distribution1D {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i] / n as f64;
+        }
+        let integral = cdf[n];
+
+        // all zero weights, fall back to uniform
+        if integral > 0.0 {
+            for value in cdf.iter_mut() {
+                *value /= integral;
+            }
+        } else {
+            for (i, value) in cdf.iter_mut().enumerate() {
+                *value = i as f64 / n as f64;
+            }
+        }
+
+        Distribution1D {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.func.is_empty()
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Maps a uniform `u` in [0, 1) to a sample, returns it with its density and its bucket.
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        // last bucket whose cdf start is <= u
+        let offset = (self.cdf.partition_point(|&c| c <= u) - 1).min(self.len() - 1);
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (u - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+
+        let x = (offset as f64 + du) / self.len() as f64;
+        (x, self.pdf_bucket(offset), offset)
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        let offset = ((x * self.len() as f64) as usize).min(self.len() - 1);
+        self.pdf_bucket(offset)
+    }
+
+    fn pdf_bucket(&self, offset: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.func[offset] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Piecewise-constant 2D distribution over [0, 1)², a marginal over the rows and one
+/// conditional distribution per row. Row `v` of `func` holds the weights for that `v` bucket.
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f64], width: usize, height: usize) -> Distribution2D {
+        let conditional: Vec<Distribution1D> = (0..height)
+            .map(|v| Distribution1D::new(func[v * width..(v + 1) * width].to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Maps two uniform numbers to a point `(u, v)` and returns it with its density.
+    pub fn sample(&self, u1: f64, u2: f64) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u2);
+        let (u, pdf_u, _) = self.conditional[row].sample(u1);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = ((v * self.marginal.len() as f64) as usize).min(self.marginal.len() - 1);
+        self.conditional[row].pdf(u) * self.marginal.pdf(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_1d_pdf() {
+        let distribution = Distribution1D::new(vec![1.0, 3.0]);
+
+        assert!(distribution.integral() == 2.0);
+        assert!(distribution.pdf(0.25) == 0.5 && distribution.pdf(0.75) == 1.5);
+    }
+
+    #[test]
+    fn test_1d_sample_inverts_cdf() {
+        let distribution = Distribution1D::new(vec![1.0, 3.0]);
+        let (x1, pdf1, offset1) = distribution.sample(0.125);
+        let (x2, pdf2, offset2) = distribution.sample(0.625);
+
+        assert!(x1 == 0.25 && pdf1 == 0.5 && offset1 == 0);
+        assert!(x2 == 0.75 && pdf2 == 1.5 && offset2 == 1);
+    }
+
+    #[test]
+    fn test_1d_skips_empty_buckets() {
+        let distribution = Distribution1D::new(vec![0.0, 1.0, 0.0, 1.0]);
+
+        for u in [0.0, 0.3, 0.5, 0.7, 0.999] {
+            let (x, pdf, _) = distribution.sample(u);
+            assert!(pdf > 0.0 && distribution.pdf(x) == pdf);
+        }
+    }
+
+    #[test]
+    fn test_1d_all_zero_is_uniform() {
+        let distribution = Distribution1D::new(vec![0.0, 0.0]);
+        let (x, pdf, _) = distribution.sample(0.75);
+
+        assert!(x == 0.75 && pdf == 1.0);
+    }
+
+    #[test]
+    fn test_2d() {
+        // only the bottom right cell has weight
+        let distribution = Distribution2D::new(&[0.0, 0.0, 0.0, 2.0], 2, 2);
+        let ((u, v), pdf) = distribution.sample(0.5, 0.5);
+
+        assert!(u >= 0.5 && v >= 0.5);
+        assert!(pdf == 4.0 && distribution.pdf(u, v) == 4.0);
+        assert!(distribution.pdf(0.25, 0.25) == 0.0);
+    }
+}
@@ -0,0 +1,206 @@
+use std::{f64::consts::PI, path::Path};
+
+use image::ImageResult;
+use rand::Rng;
+
+use crate::{
+    background::Background, distribution::Distribution2D, material::Rgb, random::RandomGenerator,
+    ray::Ray, texture, vec3::Vec3,
+};
+
+/// Equirectangular (lat-long) panorama lighting the scene from infinitely far away. The top
+/// row of the image is straight up (+j), the center column looks down -k. Directions can be
+/// importance sampled proportionally to the luminance of the map, so small bright suns don't
+/// have to be found by chance.
+pub struct EnvironmentMap {
+    pub intensity: f64, // multiplies every texel
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+    rotation: f64, // around the up axis, radians
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    // `pixels` are linear, row by row from the top
+    pub fn new(width: usize, height: usize, pixels: Vec<Rgb>) -> EnvironmentMap {
+        assert!(width > 0 && height > 0 && pixels.len() == width * height);
+
+        // rows near the poles cover less solid angle, weight them by sin(theta)
+        let mut func = Vec::with_capacity(width * height);
+        for v in 0..height {
+            let sin_theta = (PI * (v as f64 + 0.5) / height as f64).sin();
+            for u in 0..width {
+                func.push(pixels[v * width + u].luminance().max(0.0) * sin_theta);
+            }
+        }
+
+        EnvironmentMap {
+            intensity: 1.0,
+            width,
+            height,
+            distribution: Distribution2D::new(&func, width, height),
+            pixels,
+            rotation: 0.0,
+        }
+    }
+
+    /// Loads a Radiance `.hdr` or an OpenEXR file, 8-bit formats are decoded from sRGB.
+    pub fn load(path: impl AsRef<Path>) -> ImageResult<EnvironmentMap> {
+        let (width, height, pixels) = texture::load_linear(path)?;
+        Ok(EnvironmentMap::new(width, height, pixels))
+    }
+
+    // turns the panorama around the up axis, in degrees
+    pub fn with_rotation(mut self, degrees: f64) -> EnvironmentMap {
+        self.rotation = degrees.to_radians();
+        self
+    }
+
+    // (u, v) in [0, 1)² of a direction, v = 0 is straight up
+    fn direction_to_uv(&self, direction: Vec3) -> (f64, f64) {
+        let unit_dir = direction.unit_vector();
+        let theta = unit_dir.j.clamp(-1.0, 1.0).acos();
+        let phi = unit_dir.i.atan2(-unit_dir.k) - self.rotation;
+        let u = (phi / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        (u, theta / PI)
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vec3 {
+        let theta = v * PI;
+        let phi = (u - 0.5) * 2.0 * PI + self.rotation;
+        Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        )
+    }
+
+    fn texel(&self, u: f64, v: f64) -> Rgb {
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x] * self.intensity
+    }
+}
+
+impl Background for EnvironmentMap {
+    fn color(&self, ray: &Ray) -> Rgb {
+        let (u, v) = self.direction_to_uv(ray.dir());
+        self.texel(u, v)
+    }
+
+    // picks texels proportionally to their luminance, then a uniform spot inside the texel
+    fn sample(&self, random_generator: &mut RandomGenerator) -> Option<(Vec3, f64)> {
+        let ((u, v), pdf_uv) = self.distribution.sample(
+            random_generator.gen_range(0.0..1.0),
+            random_generator.gen_range(0.0..1.0),
+        );
+        let direction = self.uv_to_direction(u, v);
+        let sin_theta = (v * PI).sin();
+        let pdf = if sin_theta > 0.0 {
+            pdf_uv / (2.0 * PI * PI * sin_theta)
+        } else {
+            0.0
+        };
+        Some((direction, pdf))
+    }
+
+    fn is_sampled(&self) -> bool {
+        true
+    }
+
+    fn pdf(&self, direction: Vec3) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Point;
+
+    // a dim gray sky with one very bright texel
+    fn sun_map() -> EnvironmentMap {
+        let (width, height) = (32, 16);
+        let mut pixels = vec![Rgb::new(0.1, 0.1, 0.1); width * height];
+        pixels[5 * width + 20] = Rgb::new(1000.0, 1000.0, 1000.0);
+        EnvironmentMap::new(width, height, pixels)
+    }
+
+    fn is_sun(direction: Vec3) -> bool {
+        let map = sun_map();
+        let (u, v) = map.direction_to_uv(direction);
+        ((u * 32.0) as usize, (v * 16.0) as usize) == (20, 5)
+    }
+
+    #[test]
+    fn test_uv_round_trip() {
+        let map = sun_map().with_rotation(30.0);
+        for (u, v) in [(0.1, 0.2), (0.5, 0.5), (0.9, 0.7), (0.3, 0.95)] {
+            let (u2, v2) = map.direction_to_uv(map.uv_to_direction(u, v));
+            assert!((u - u2).abs() < 1e-9 && (v - v2).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_orientation() {
+        let map = sun_map();
+        let (_, v_up) = map.direction_to_uv(Vec3::new(0.0, 1.0, 0.0));
+        let (u_front, v_front) = map.direction_to_uv(Vec3::new(0.0, 0.0, -1.0));
+
+        assert!(v_up == 0.0);
+        assert!(u_front == 0.5 && v_front == 0.5);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let map = sun_map();
+        let ray = Ray::new(
+            Point::new(0.0, 0.0, 0.0),
+            map.uv_to_direction(20.5 / 32.0, 5.5 / 16.0),
+        );
+
+        assert!(map.color(&ray).r == 1000.0);
+    }
+
+    #[test]
+    fn test_sampling_favors_the_sun() {
+        let map = sun_map();
+        assert!(map.is_sampled());
+        let mut random_generator = RandomGenerator::new(1);
+        let hits = (0..1000)
+            .filter(|_| is_sun(map.sample(&mut random_generator).unwrap().0))
+            .count();
+
+        assert!(hits > 900);
+    }
+
+    #[test]
+    fn test_sample_pdf_matches_pdf() {
+        let map = sun_map();
+        let mut random_generator = RandomGenerator::new(2);
+        for _ in 0..100 {
+            let (direction, pdf) = map.sample(&mut random_generator).unwrap();
+            assert!((map.pdf(direction) - pdf).abs() <= 1e-6 * pdf);
+        }
+    }
+
+    #[test]
+    fn test_pdf_integrates_to_one() {
+        // Monte Carlo estimate of the integral over the sphere with uniform directions
+        let map = sun_map();
+        let mut random_generator = RandomGenerator::new(3);
+        let n = 200_000;
+        let sum: f64 = (0..n)
+            .map(|_| map.pdf(Vec3::random_unit_vector(&mut random_generator)))
+            .sum();
+        let integral = sum / n as f64 * 4.0 * PI;
+
+        assert!((integral - 1.0).abs() < 0.05);
+    }
+}
@@ -0,0 +1,159 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use image::{codecs::hdr::HdrEncoder, ImageError, ImageResult, Rgb32FImage};
+
+use crate::{material::Rgb, tonemap::ToneMapping};
+
+/// Linear radiance of every pixel, already averaged over its samples. Nothing is clipped
+/// until the buffer is written to an 8-bit format.
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Rgb::new(0.0, 0.0, 0.0); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // i is the row, j the column, as in `Camera`
+    pub fn get(&self, i: usize, j: usize) -> Rgb {
+        self.pixels[i * self.width + j]
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, color: Rgb) {
+        self.pixels[i * self.width + j] = color;
+    }
+
+    /// Saves the buffer, the format is picked from the extension: `.exr` and `.hdr` keep the
+    /// linear floats, anything else goes through `tone_mapping` and the sRGB curve into 8 bits
+    /// per channel.
+    pub fn save(&self, path: impl AsRef<Path>, tone_mapping: &ToneMapping) -> ImageResult<()> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("exr") => self.to_rgb32f().save(path),
+            Some("hdr") => self.save_hdr(path),
+            _ => image::save_buffer(
+                path,
+                &self.to_rgb8(tone_mapping),
+                self.width as u32,
+                self.height as u32,
+                image::ColorType::Rgb8,
+            ),
+        }
+    }
+
+    fn to_rgb8(&self, tone_mapping: &ToneMapping) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 3);
+        for color in &self.pixels {
+            let color_display = tone_mapping.apply(*color).to_srgb() * 255.0;
+            bytes.push(color_display.r.round() as u8);
+            bytes.push(color_display.g.round() as u8);
+            bytes.push(color_display.b.round() as u8);
+        }
+        bytes
+    }
+
+    fn to_rgb32f(&self) -> Rgb32FImage {
+        let floats = self
+            .pixels
+            .iter()
+            .flat_map(|color| [color.r as f32, color.g as f32, color.b as f32])
+            .collect();
+        Rgb32FImage::from_raw(self.width as u32, self.height as u32, floats).unwrap()
+    }
+
+    fn save_hdr(&self, path: &Path) -> ImageResult<()> {
+        let file = File::create(path).map_err(ImageError::IoError)?;
+        let pixels: Vec<image::Rgb<f32>> = self.to_rgb32f().pixels().copied().collect();
+        HdrEncoder::new(BufWriter::new(file)).encode(&pixels, self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tonemap::ToneMapper;
+
+    fn framebuffer() -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(3, 2);
+        framebuffer.set(0, 0, Rgb::new(4.0, 0.5, 0.0));
+        framebuffer.set(1, 2, Rgb::new(0.25, 1.0, 16.0));
+        framebuffer
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join("rtoneweekend_framebuffer_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    #[test]
+    fn test_get_set() {
+        let framebuffer = framebuffer();
+
+        assert!(framebuffer.get(0, 0).r == 4.0);
+        assert!(framebuffer.get(1, 2).b == 16.0);
+        assert!(framebuffer.get(1, 0).g == 0.0);
+    }
+
+    #[test]
+    fn test_save_exr_keeps_range() {
+        let path = temp_path("test.exr");
+        framebuffer().save(&path, &ToneMapping::default()).unwrap();
+        let image = image::open(&path).unwrap().into_rgb32f();
+
+        assert!(image.dimensions() == (3, 2));
+        assert!(image.get_pixel(0, 0).0 == [4.0, 0.5, 0.0]);
+        assert!(image.get_pixel(2, 1).0 == [0.25, 1.0, 16.0]);
+    }
+
+    #[test]
+    fn test_save_hdr_keeps_range() {
+        let path = temp_path("test.hdr");
+        framebuffer().save(&path, &ToneMapping::default()).unwrap();
+        // `image::open` would squash .hdr files to 8 bits, read the floats directly
+        let reader = std::io::BufReader::new(File::open(&path).unwrap());
+        let pixels = image::codecs::hdr::HdrDecoder::new(reader)
+            .unwrap()
+            .read_image_hdr()
+            .unwrap();
+
+        // RGBE shares the exponent between channels, these values survive exactly
+        assert!(pixels.len() == 6);
+        assert!(pixels[0].0 == [4.0, 0.5, 0.0]);
+        assert!(pixels[5].0 == [0.25, 1.0, 16.0]);
+    }
+
+    #[test]
+    fn test_save_png_clips() {
+        let path = temp_path("test.png");
+        let clamp = ToneMapping {
+            tone_mapper: ToneMapper::Clamp,
+            ..ToneMapping::default()
+        };
+        framebuffer().save(&path, &clamp).unwrap();
+        let image = image::open(&path).unwrap().into_rgb8();
+
+        assert!(image.get_pixel(0, 0).0 == [255, 188, 0]);
+        assert!(image.get_pixel(1, 0).0 == [0, 0, 0]);
+    }
+}
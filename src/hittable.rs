@@ -1,9 +1,13 @@
-use std::rc::Rc;
+use std::sync::Arc;
+
+use rand::Rng;
 
 use crate::{
+    aabb::Aabb,
     material::Material,
+    random::RandomGenerator,
     ray::Ray,
-    vec3::{Point, Vec3},
+    vec3::{AnimatedTransform, Point, Transform, Vec3},
 };
 
 pub struct HitRecord {
@@ -11,7 +15,10 @@ pub struct HitRecord {
     pub t: f64,
     pub normal: Vec3, // unit vector
     pub out_facing: bool,
-    pub material: Rc<dyn Material>,
+    // surface coordinates of the hit, barycentric for triangles and uv for textured meshes
+    pub u: f64,
+    pub v: f64,
+    pub material: Arc<dyn Material>,
 }
 
 impl HitRecord {
@@ -27,11 +34,26 @@ impl HitRecord {
     }
 }
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord>;
+
+    // box enclosing the whole object, used to build the BVH
+    fn bounding_box(&self) -> Aabb;
+
+    // density per steradian of `sample` picking the direction of `ray` from its origin, 0 for
+    // the objects that can't be sampled, which are never aimed at as lights
+    fn pdf_value(&self, _ray: &Ray) -> f64 {
+        0.0
+    }
+
+    // a direction from `origin` toward a random point of the object at `time`, not a unit
+    // vector
+    fn sample(&self, _origin: Point, _time: f64, _random_generator: &mut RandomGenerator) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
 }
 
-pub type HittableList = Vec<Rc<dyn Hittable>>;
+pub type HittableList = Vec<Arc<dyn Hittable>>;
 
 impl Hittable for HittableList {
     fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
@@ -45,6 +67,263 @@ impl Hittable for HittableList {
             }
         }
 
-        return hit_record;
+        hit_record
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.iter().fold(Aabb::empty(), |bbox, shape| {
+            bbox.surrounding(&shape.bounding_box())
+        })
+    }
+
+    // every object is as likely to be picked, the lights of a scene are sampled this way
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self.iter().map(|shape| shape.pdf_value(ray)).sum();
+        sum / self.len() as f64
+    }
+
+    fn sample(&self, origin: Point, time: f64, random_generator: &mut RandomGenerator) -> Vec3 {
+        if self.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let ix = random_generator.gen_range(0..self.len());
+        self[ix].sample(origin, time, random_generator)
+    }
+}
+
+/// Another object moved by an affine transform. Rays are taken into the object's own space
+/// and the hit is brought back out, so the object itself is shared: the same mesh can be
+/// placed any number of times without copying its triangles. The transform can also change
+/// over time, following the ray's time for motion blur.
+pub struct Transformed {
+    object: Arc<dyn Hittable>,
+    motion: AnimatedTransform,
+    bbox: Aabb,
+}
+
+impl Transformed {
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Transformed {
+        Transformed::animated(object, transform.into())
+    }
+
+    pub fn animated(object: Arc<dyn Hittable>, motion: AnimatedTransform) -> Transformed {
+        let bbox = motion_box(&object.bounding_box(), &motion);
+        Transformed {
+            object,
+            motion,
+            bbox,
+        }
+    }
+}
+
+// how many poses of an animated transform are looked at to bound its motion
+const MOTION_BOX_STEPS: usize = 64;
+
+fn corners(bbox: &Aabb) -> [Point; 8] {
+    std::array::from_fn(|corner| {
+        let pick = |axis: usize| {
+            if corner & (1 << axis) == 0 {
+                bbox.min[axis]
+            } else {
+                bbox.max[axis]
+            }
+        };
+        Point::new(pick(0), pick(1), pick(2))
+    })
+}
+
+// box around the transformed `bbox` over the whole motion
+fn motion_box(bbox: &Aabb, motion: &AnimatedTransform) -> Aabb {
+    let finite = (0..3).all(|axis| bbox.min[axis].is_finite() && bbox.max[axis].is_finite());
+    if !finite {
+        // the corners would be NaNs, and an unbounded shape stays unbounded when rotated
+        return if bbox.min.i > bbox.max.i {
+            Aabb::empty()
+        } else {
+            Aabb::infinite()
+        };
+    }
+
+    let steps = if motion.is_animated() {
+        MOTION_BOX_STEPS
+    } else {
+        0
+    };
+    let mut result = Aabb::empty();
+    let mut previous: Option<[Point; 8]> = None;
+    let mut longest_step: f64 = 0.0;
+    for step in 0..=steps {
+        let transform = motion.at(step as f64 / steps.max(1) as f64);
+        let current = corners(bbox).map(|corner| transform.point(corner));
+        for (ix, &corner) in current.iter().enumerate() {
+            result = result.surrounding(&Aabb::new(corner, corner));
+            if let Some(previous) = previous {
+                longest_step = longest_step.max((corner - previous[ix]).length());
+            }
+        }
+        previous = Some(current);
+    }
+
+    // between two poses a corner follows a short arc, it stays within a step of both ends
+    let padding = Vec3::new(longest_step, longest_step, longest_step);
+    Aabb::new(result.min - padding, result.max + padding)
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        let transform = self.motion.at(ray.time());
+        // the direction isn't normalized, so t means the same thing in both spaces
+        let local = Ray::with_time(
+            transform.inverse().point(ray.origin()),
+            transform.inverse().vector(ray.dir()),
+            ray.time(),
+        );
+        let mut hit_record = self.object.hit(&local, ray_tmin, ray_tmax)?;
+
+        // the normal keeps its side of the surface: n.d doesn't change through the transform
+        hit_record.intersection = transform.point(hit_record.intersection);
+        hit_record.normal = transform.normal(hit_record.normal).unit_vector();
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        let transform = self.motion.at(ray.time());
+        let local_direction = transform.inverse().vector(ray.dir());
+        let local = Ray::with_time(
+            transform.inverse().point(ray.origin()),
+            local_direction,
+            ray.time(),
+        );
+        let pdf = self.object.pdf_value(&local);
+        if pdf == 0.0 {
+            return 0.0;
+        }
+
+        // the transform squeezes or spreads the directions around the origin: a solid angle
+        // grows by |det M| / |M d|^3 for the unit local direction d
+        let stretch = ray.dir().length() / local_direction.length();
+        pdf * stretch.powi(3) / transform.matrix.determinant3().abs()
+    }
+
+    fn sample(&self, origin: Point, time: f64, random_generator: &mut RandomGenerator) -> Vec3 {
+        let transform = self.motion.at(time);
+        let local_origin = transform.inverse().point(origin);
+        transform.vector(self.object.sample(local_origin, time, random_generator))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::{Lambertian, Rgb},
+        shape::{Plane, Sphere},
+    };
+
+    fn sphere() -> Arc<dyn Hittable> {
+        Arc::new(Sphere::new(
+            Point::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Rgb::new(0.5, 0.5, 0.5))),
+        ))
+    }
+
+    #[test]
+    fn test_transformed_sphere() {
+        // an ellipsoid twice as wide along i, moved to (5, 0, 0)
+        let transform = Transform::scaling(Vec3::new(2.0, 1.0, 1.0))
+            .then(&Transform::translation(Vec3::new(5.0, 0.0, 0.0)));
+        let ellipsoid = Transformed::new(sphere(), transform);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit_record = ellipsoid.hit(&ray, 0.0, f64::INFINITY).unwrap();
+
+        assert!((hit_record.t - 3.0).abs() < 1e-12 && hit_record.out_facing);
+        assert!((hit_record.intersection - Point::new(3.0, 0.0, 0.0)).length() < 1e-12);
+        assert!((hit_record.normal - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-12);
+
+        let bbox = ellipsoid.bounding_box();
+        assert!(bbox.min.i == 3.0 && bbox.max.i == 7.0 && bbox.max.j == 1.0);
+    }
+
+    #[test]
+    fn test_transformed_normal() {
+        // the normal of a squashed sphere isn't the direction from its center
+        let squashed = Transformed::new(sphere(), Transform::scaling(Vec3::new(1.0, 0.5, 1.0)));
+        let p = 0.5_f64.sqrt();
+        let ray = Ray::new(Point::new(p, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit_record = squashed.hit(&ray, 0.0, f64::INFINITY).unwrap();
+
+        assert!((hit_record.intersection.j - 0.5 * p).abs() < 1e-12);
+        assert!((hit_record.normal - Vec3::new(1.0, 2.0, 0.0).unit_vector()).length() < 1e-12);
+    }
+
+    #[test]
+    fn test_shared_object() {
+        let object = sphere();
+        let instances: HittableList = (0..3)
+            .map(|ix| {
+                let offset = Transform::translation(Vec3::new(0.0, 0.0, -3.0 * ix as f64));
+                Arc::new(Transformed::new(object.clone(), offset)) as Arc<dyn Hittable>
+            })
+            .collect();
+        let ray = Ray::new(Point::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+
+        assert!(Arc::strong_count(&object) == 4);
+        assert!((instances.hit(&ray, 0.0, f64::INFINITY).unwrap().t - 9.0).abs() < 1e-12);
+        let behind = Ray::new(Point::new(0.0, 0.0, -4.5), Vec3::new(0.0, 0.0, -1.0));
+        assert!((instances.hit(&behind, 0.0, f64::INFINITY).unwrap().t - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_moving_objects() {
+        let material = Arc::new(Lambertian::new(Rgb::new(0.5, 0.5, 0.5)));
+        let moving = Sphere::moving(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 2.0, 0.0),
+            1.0,
+            material,
+        );
+        let spinning = Transformed::animated(
+            sphere(),
+            AnimatedTransform::new(
+                Transform::translation(Vec3::new(2.0, 0.0, 0.0)),
+                Transform::translation(Vec3::new(2.0, 0.0, 0.0))
+                    .then(&Transform::rotation(Vec3::new(0.0, 0.0, 1.0), 180.0)),
+            ),
+        );
+        let at =
+            |time: f64| Ray::with_time(Point::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0), time);
+
+        assert!((moving.hit(&at(0.0), 0.0, f64::INFINITY).unwrap().t - 9.0).abs() < 1e-12);
+        assert!(moving.hit(&at(1.0), 0.0, f64::INFINITY).is_none());
+        assert!(moving.bounding_box().max.j == 3.0);
+        // the translation goes from (2, 0, 0) to (-2, 0, 0) and is at the origin halfway
+        assert!(spinning.hit(&at(0.0), 0.0, f64::INFINITY).is_none());
+        assert!((spinning.hit(&at(0.5), 0.0, f64::INFINITY).unwrap().t - 9.0).abs() < 1e-9);
+        let bbox = spinning.bounding_box();
+        assert!(bbox.max.i >= 3.0 && bbox.min.i <= -3.0);
+    }
+
+    #[test]
+    fn test_transformed_plane() {
+        let material = Arc::new(Lambertian::new(Rgb::new(0.5, 0.5, 0.5)));
+        let plane = Plane::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            material,
+        );
+        let tilted = Transformed::new(
+            Arc::new(plane),
+            Transform::rotation(Vec3::new(0.0, 0.0, 1.0), 45.0),
+        );
+
+        assert!(tilted.bounding_box().max.i == f64::INFINITY);
     }
 }
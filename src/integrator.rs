@@ -0,0 +1,877 @@
+use std::{fmt, str::FromStr, sync::Arc};
+
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    background::Background,
+    hittable::{HitRecord, Hittable, HittableList},
+    material::{Rgb, Scatter},
+    pdf::{balance_heuristic, power_heuristic, BackgroundPdf, HittablePdf, MixturePdf, Pdf},
+    random::RandomGenerator,
+    ray::Ray,
+    vec3::{Point, Vec3},
+};
+
+/// The scene as the integrators see it, put together by `Camera::render`.
+pub struct SceneView<'a> {
+    pub world: &'a dyn Hittable,
+    pub lights: &'a HittableList, // the emissive shapes of `world` worth sending rays toward
+    pub background: &'a dyn Background,
+    pub sample_background: bool, // whether `background` supports sampling
+    pub float_correction: f64,   // hits closer than this are the surface the ray starts from
+    pub max_depth: usize,        // longest path, in bounces
+}
+
+impl SceneView<'_> {
+    pub fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        self.world.hit(ray, self.float_correction, f64::INFINITY)
+    }
+
+    /// A direction toward one of the lights or the sampled background, picked with the same
+    /// chance, `None` when there's neither.
+    pub fn sample_light(
+        &self,
+        origin: Point,
+        time: f64,
+        random_generator: &mut RandomGenerator,
+    ) -> Option<Vec3> {
+        let lights = self.lights;
+        match (lights.is_empty(), self.sample_background) {
+            (true, false) => None,
+            (false, false) => Some(lights.sample(origin, time, random_generator)),
+            (true, true) => self.background.sample(random_generator).map(|(d, _)| d),
+            (false, true) => match random_generator.gen_range(0..2) {
+                0 => Some(lights.sample(origin, time, random_generator)),
+                _ => self.background.sample(random_generator).map(|(d, _)| d),
+            },
+        }
+    }
+
+    /// Density of `sample_light` picking the direction of `ray` from its origin.
+    pub fn light_pdf(&self, ray: &Ray) -> f64 {
+        let background_pdf = || self.background.pdf(ray.dir().unit_vector());
+        match (self.lights.is_empty(), self.sample_background) {
+            (true, false) => 0.0,
+            (false, false) => self.lights.pdf_value(ray),
+            (true, true) => background_pdf(),
+            (false, true) => 0.5 * (self.lights.pdf_value(ray) + background_pdf()),
+        }
+    }
+
+    /// What a light sample sees: the glow of whatever it hits first, or the background when
+    /// it's sampled too. A bounce hitting the same thing gets the other share of the MIS
+    /// weight, so emitters left out of `lights`, like obj meshes and glowing fog, must count
+    /// here as well.
+    pub fn light_radiance(&self, ray: &Ray) -> Rgb {
+        match self.hit(ray) {
+            Some(hit_record) => hit_record.material.emitted(&hit_record),
+            None if self.sample_background => self.background.color(ray),
+            None => Rgb::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Computes the light coming back along a camera ray, the camera averages it over the samples
+/// of each pixel.
+pub trait Integrator: Send + Sync {
+    fn radiance(&self, ray: &Ray, scene: &SceneView, random_generator: &mut RandomGenerator)
+        -> Rgb;
+}
+
+/// How the light reaching diffuse surfaces is found, when the scene has lights or a background
+/// that supports sampling.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LightSampling {
+    None,    // only the material's bounces, the lights are found by chance like the rest
+    Mixture, // one bounce picked from the material or toward a light, with their average density
+    Balance, // a light sample plus a material bounce, weighted with the balance heuristic
+    Power,   // same with the power heuristic
+}
+
+impl LightSampling {
+    pub const NAMES: [&'static str; 4] = ["none", "mixture", "balance", "power"];
+
+    fn weight(self, pdf: f64, other: f64) -> f64 {
+        match self {
+            LightSampling::Balance => balance_heuristic(pdf, other),
+            _ => power_heuristic(pdf, other),
+        }
+    }
+}
+
+impl FromStr for LightSampling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LightSampling, String> {
+        match s {
+            "none" => Ok(LightSampling::None),
+            "mixture" => Ok(LightSampling::Mixture),
+            "balance" => Ok(LightSampling::Balance),
+            "power" => Ok(LightSampling::Power),
+            _ => Err(format!(
+                "unknown light sampling `{}`, expected one of {}",
+                s,
+                LightSampling::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LightSampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ix = match self {
+            LightSampling::None => 0,
+            LightSampling::Mixture => 1,
+            LightSampling::Balance => 2,
+            LightSampling::Power => 3,
+        };
+        f.write_str(LightSampling::NAMES[ix])
+    }
+}
+
+/// The names `by_name` knows.
+pub const INTEGRATOR_NAMES: [&str; 6] = [
+    "path",
+    "whitted",
+    "normals",
+    "depth",
+    "albedo",
+    "ambient_occlusion",
+];
+
+/// One of the integrators with its default settings.
+pub fn by_name(name: &str) -> Result<Arc<dyn Integrator>, String> {
+    match name {
+        "path" => Ok(Arc::new(PathTracer::default())),
+        "whitted" => Ok(Arc::new(Whitted)),
+        "normals" => Ok(Arc::new(Normals)),
+        "depth" => Ok(Arc::new(Depth::default())),
+        "albedo" => Ok(Arc::new(Albedo)),
+        "ambient_occlusion" => Ok(Arc::new(AmbientOcclusion::default())),
+        _ => Err(format!(
+            "unknown integrator `{}`, expected one of {}",
+            name,
+            INTEGRATOR_NAMES.join(", ")
+        )),
+    }
+}
+
+// the light sample of a diffuse bounce from `hit_record`, weighted against the material's own
+// sampling `pdf` with `light_sampling`
+fn sample_direct_light(
+    scene: &SceneView,
+    ray_in: &Ray,
+    hit_record: &HitRecord,
+    pdf: &dyn Pdf,
+    light_sampling: LightSampling,
+    random_generator: &mut RandomGenerator,
+) -> Rgb {
+    let black = Rgb::new(0.0, 0.0, 0.0);
+    let origin = hit_record.intersection;
+    let Some(direction) = scene.sample_light(origin, ray_in.time(), random_generator) else {
+        return black;
+    };
+    let light_ray = Ray::with_time(origin, direction, ray_in.time());
+    let light_pdf = scene.light_pdf(&light_ray);
+    if light_pdf <= 0.0 {
+        return black;
+    }
+
+    let scattering = hit_record
+        .material
+        .scattering(ray_in, hit_record, &light_ray);
+    let weight = light_sampling.weight(light_pdf, pdf.value(direction));
+    scene.light_radiance(&light_ray) * scattering * (weight / light_pdf)
+}
+
+// paths can be stopped by Russian roulette from this many bounces on
+const ROULETTE_MIN_BOUNCES: usize = 3;
+// even a path bringing back all the light has this chance to go on, or paths between mirrors
+// and glass could only end at `max_depth`
+const ROULETTE_MAX_SURVIVAL: f64 = 0.95;
+
+// how far a path went, and the fraction of the light found from there on that makes it back
+// to the camera
+#[derive(Clone, Copy, Debug)]
+struct PathState {
+    bounces: usize,
+    throughput: Rgb,
+}
+
+impl PathState {
+    fn start() -> PathState {
+        PathState {
+            bounces: 0,
+            throughput: Rgb::white(),
+        }
+    }
+
+    // the path after a bounce scaling its light by `factor`, and the weight of what it finds
+    // further on. Paths carrying little light are stopped at random with Russian roulette,
+    // the weight of the survivors makes up for them so the average stays the same.
+    fn bounce(
+        self,
+        factor: Rgb,
+        random_generator: &mut RandomGenerator,
+    ) -> Option<(PathState, f64)> {
+        let throughput = self.throughput * factor;
+        let survival = match self.bounces + 1 < ROULETTE_MIN_BOUNCES {
+            true => 1.0,
+            false => throughput.max_component().min(ROULETTE_MAX_SURVIVAL),
+        };
+        if survival < 1.0 && random_generator.gen_range(0.0..1.0) >= survival {
+            return None;
+        }
+
+        let next = PathState {
+            bounces: self.bounces + 1,
+            throughput: throughput * (1.0 / survival),
+        };
+        Some((next, 1.0 / survival))
+    }
+}
+
+/// Follows the light bouncing around the scene, the one to use for the final images. Paths
+/// end when they stop bringing back much light, `max_depth` is only a safety net.
+#[derive(Clone, Copy, Debug)]
+pub struct PathTracer {
+    pub light_sampling: LightSampling,
+}
+
+impl Default for PathTracer {
+    fn default() -> PathTracer {
+        PathTracer {
+            light_sampling: LightSampling::Power,
+        }
+    }
+}
+
+impl PathTracer {
+    fn mixture(
+        &self,
+        r: &Ray,
+        scene: &SceneView,
+        random_generator: &mut RandomGenerator,
+        path: PathState,
+    ) -> Rgb {
+        if path.bounces >= scene.max_depth {
+            return Rgb::new(0.0, 0.0, 0.0);
+        }
+
+        let Some(hit_record) = scene.hit(r) else {
+            return scene.background.color(r);
+        };
+        let emitted = hit_record.material.emitted(&hit_record);
+        match hit_record
+            .material
+            .scatter(r, &hit_record, random_generator)
+        {
+            Some(Scatter::Specular { ray, attenuation }) => {
+                match path.bounce(attenuation, random_generator) {
+                    Some((next, weight)) => {
+                        emitted
+                            + self.mixture(&ray, scene, random_generator, next)
+                                * attenuation
+                                * weight
+                    }
+                    None => emitted,
+                }
+            }
+            Some(Scatter::Diffuse { pdf }) => {
+                // half the rays, or a third with a sampled background, go where the
+                // light comes from instead of where the material prefers, unless the light
+                // sampling is off
+                let towards_lights =
+                    HittablePdf::new(scene.lights, hit_record.intersection, r.time());
+                let towards_background = BackgroundPdf::new(scene.background);
+                let mut pdfs: Vec<&dyn Pdf> = vec![pdf.as_ref()];
+                let sample_lights = self.light_sampling != LightSampling::None;
+                if sample_lights && !scene.lights.is_empty() {
+                    pdfs.push(&towards_lights);
+                }
+                if sample_lights && scene.sample_background {
+                    pdfs.push(&towards_background);
+                }
+                let mixture = MixturePdf::new(pdfs);
+
+                let scattered = Ray::with_time(
+                    hit_record.intersection,
+                    mixture.generate(random_generator),
+                    r.time(),
+                );
+                let pdf_value = mixture.value(scattered.dir());
+                if pdf_value <= 0.0 {
+                    return emitted;
+                }
+                let factor =
+                    hit_record.material.scattering(r, &hit_record, &scattered) * (1.0 / pdf_value);
+
+                match path.bounce(factor, random_generator) {
+                    Some((next, weight)) => {
+                        emitted
+                            + self.mixture(&scattered, scene, random_generator, next)
+                                * factor
+                                * weight
+                    }
+                    None => emitted,
+                }
+            }
+            None => emitted,
+        }
+    }
+
+    // `bsdf_pdf` is the density the material picked `r` with, `None` for the camera rays and
+    // the specular bounces that no light sample could have found
+    fn mis(
+        &self,
+        r: &Ray,
+        scene: &SceneView,
+        random_generator: &mut RandomGenerator,
+        path: PathState,
+        bsdf_pdf: Option<f64>,
+    ) -> Rgb {
+        if path.bounces >= scene.max_depth {
+            return Rgb::new(0.0, 0.0, 0.0);
+        }
+
+        // the light found by the material's bounce, its share is what the light samples miss
+        let weight = match bsdf_pdf {
+            Some(pdf) => self.light_sampling.weight(pdf, scene.light_pdf(r)),
+            None => 1.0,
+        };
+        let Some(hit_record) = scene.hit(r) else {
+            return scene.background.color(r) * weight;
+        };
+        let mut color = hit_record.material.emitted(&hit_record) * weight;
+
+        match hit_record
+            .material
+            .scatter(r, &hit_record, random_generator)
+        {
+            Some(Scatter::Specular { ray, attenuation }) => {
+                match path.bounce(attenuation, random_generator) {
+                    Some((next, weight)) => {
+                        color
+                            + self.mis(&ray, scene, random_generator, next, None)
+                                * attenuation
+                                * weight
+                    }
+                    None => color,
+                }
+            }
+            Some(Scatter::Diffuse { pdf }) => {
+                // the light would be one bounce further, skip it when that's past the last one
+                if path.bounces + 1 < scene.max_depth {
+                    color = color
+                        + sample_direct_light(
+                            scene,
+                            r,
+                            &hit_record,
+                            pdf.as_ref(),
+                            self.light_sampling,
+                            random_generator,
+                        );
+                }
+
+                let scattered = Ray::with_time(
+                    hit_record.intersection,
+                    pdf.generate(random_generator),
+                    r.time(),
+                );
+                let pdf_value = pdf.value(scattered.dir());
+                if pdf_value <= 0.0 {
+                    return color;
+                }
+                let factor =
+                    hit_record.material.scattering(r, &hit_record, &scattered) * (1.0 / pdf_value);
+
+                match path.bounce(factor, random_generator) {
+                    Some((next, weight)) => {
+                        color
+                            + self.mis(&scattered, scene, random_generator, next, Some(pdf_value))
+                                * factor
+                                * weight
+                    }
+                    None => color,
+                }
+            }
+            None => color,
+        }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        scene: &SceneView,
+        random_generator: &mut RandomGenerator,
+    ) -> Rgb {
+        match self.light_sampling {
+            LightSampling::None | LightSampling::Mixture => {
+                self.mixture(ray, scene, random_generator, PathState::start())
+            }
+            _ => self.mis(ray, scene, random_generator, PathState::start(), None),
+        }
+    }
+}
+
+/// Mirrors and glass are followed as in a path tracer, but the other surfaces only see the
+/// light coming straight from the lights and the background. Fast and noise free on simple
+/// scenes, without any of the light bouncing between walls.
+#[derive(Clone, Copy, Debug)]
+pub struct Whitted;
+
+impl Whitted {
+    fn trace(
+        &self,
+        r: &Ray,
+        scene: &SceneView,
+        random_generator: &mut RandomGenerator,
+        depth: usize,
+    ) -> Rgb {
+        if depth == 0 {
+            return Rgb::new(0.0, 0.0, 0.0);
+        }
+
+        let Some(hit_record) = scene.hit(r) else {
+            return scene.background.color(r);
+        };
+        let emitted = hit_record.material.emitted(&hit_record);
+        match hit_record
+            .material
+            .scatter(r, &hit_record, random_generator)
+        {
+            Some(Scatter::Specular { ray, attenuation }) => {
+                emitted + self.trace(&ray, scene, random_generator, depth - 1) * attenuation
+            }
+            Some(Scatter::Diffuse { pdf }) => {
+                let light = sample_direct_light(
+                    scene,
+                    r,
+                    &hit_record,
+                    pdf.as_ref(),
+                    LightSampling::Power,
+                    random_generator,
+                );
+
+                // a bounce picked by the material finds the lights too small or too close for
+                // the light samples, and the background when it can't be sampled
+                let scattered = Ray::with_time(
+                    hit_record.intersection,
+                    pdf.generate(random_generator),
+                    r.time(),
+                );
+                let pdf_value = pdf.value(scattered.dir());
+                if pdf_value <= 0.0 {
+                    return emitted + light;
+                }
+                let found = match scene.hit(&scattered) {
+                    Some(next) => next.material.emitted(&next),
+                    None => scene.background.color(&scattered),
+                };
+                let weight = power_heuristic(pdf_value, scene.light_pdf(&scattered));
+                let scattering = hit_record.material.scattering(r, &hit_record, &scattered);
+
+                emitted + light + found * scattering * (weight / pdf_value)
+            }
+            None => emitted,
+        }
+    }
+}
+
+impl Integrator for Whitted {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        scene: &SceneView,
+        random_generator: &mut RandomGenerator,
+    ) -> Rgb {
+        self.trace(ray, scene, random_generator, scene.max_depth)
+    }
+}
+
+/// The normal at the first hit, facing the camera, its components mapped from [-1, 1] to
+/// [0, 1]. Black where nothing is hit.
+#[derive(Clone, Copy, Debug)]
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn radiance(&self, ray: &Ray, scene: &SceneView, _: &mut RandomGenerator) -> Rgb {
+        match scene.hit(ray) {
+            Some(hit_record) => {
+                let n = hit_record.normal;
+                Rgb::new(0.5 * (n.i + 1.0), 0.5 * (n.j + 1.0), 0.5 * (n.k + 1.0))
+            }
+            None => Rgb::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Distance from the camera to the first hit, white up close fading to black at
+/// `max_distance` and beyond.
+#[derive(Clone, Copy, Debug)]
+pub struct Depth {
+    pub max_distance: f64,
+}
+
+impl Default for Depth {
+    fn default() -> Depth {
+        Depth { max_distance: 20.0 }
+    }
+}
+
+impl Integrator for Depth {
+    fn radiance(&self, ray: &Ray, scene: &SceneView, _: &mut RandomGenerator) -> Rgb {
+        match scene.hit(ray) {
+            Some(hit_record) => {
+                let distance = hit_record.t * ray.dir().length();
+                Rgb::white() * (1.0 - distance / self.max_distance).clamp(0.0, 1.0)
+            }
+            None => Rgb::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// The color of the first surface hit without any lighting: the fraction of the light its
+/// material reflects, the light of emissive ones and the background where nothing is hit.
+#[derive(Clone, Copy, Debug)]
+pub struct Albedo;
+
+impl Integrator for Albedo {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        scene: &SceneView,
+        random_generator: &mut RandomGenerator,
+    ) -> Rgb {
+        let Some(hit_record) = scene.hit(ray) else {
+            return scene.background.color(ray);
+        };
+        match hit_record
+            .material
+            .scatter(ray, &hit_record, random_generator)
+        {
+            Some(Scatter::Specular { attenuation, .. }) => attenuation,
+            // what a bounce brings back on average, just the color of a matte surface
+            Some(Scatter::Diffuse { pdf }) => {
+                let scattered = Ray::with_time(
+                    hit_record.intersection,
+                    pdf.generate(random_generator),
+                    ray.time(),
+                );
+                let pdf_value = pdf.value(scattered.dir());
+                if pdf_value <= 0.0 {
+                    return Rgb::new(0.0, 0.0, 0.0);
+                }
+                hit_record.material.scattering(ray, &hit_record, &scattered) * (1.0 / pdf_value)
+            }
+            None => hit_record.material.emitted(&hit_record),
+        }
+    }
+}
+
+/// White where a cosine weighted ray from the first hit escapes further than `distance`, black
+/// where it's blocked, so creases and contacts come out darker. Nothing hit is white.
+#[derive(Clone, Copy, Debug)]
+pub struct AmbientOcclusion {
+    pub distance: f64,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> AmbientOcclusion {
+        AmbientOcclusion { distance: 1.0 }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        scene: &SceneView,
+        random_generator: &mut RandomGenerator,
+    ) -> Rgb {
+        let Some(hit_record) = scene.hit(ray) else {
+            return Rgb::white();
+        };
+        let v = Vec3::random_cosine_direction(random_generator);
+        let (tangent, bitangent) = hit_record.normal.tangent_frame();
+        let direction = v.i * tangent + v.j * bitangent + v.k * hit_record.normal;
+        let probe = Ray::with_time(hit_record.intersection, direction, ray.time());
+
+        match scene
+            .world
+            .hit(&probe, scene.float_correction, self.distance)
+        {
+            Some(_) => Rgb::new(0.0, 0.0, 0.0),
+            None => Rgb::white(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    use crate::{
+        material::{DiffuseLight, Lambertian, Material},
+        pdf::CosinePdf,
+        shape::{Quad, Sphere},
+    };
+
+    fn view<'a>(
+        world: &'a HittableList,
+        lights: &'a HittableList,
+        background: &'a dyn Background,
+    ) -> SceneView<'a> {
+        SceneView {
+            world,
+            lights,
+            background,
+            sample_background: false,
+            float_correction: 0.0001,
+            max_depth: 10,
+        }
+    }
+
+    fn average(integrator: &dyn Integrator, scene: &SceneView, ray: &Ray, samples: usize) -> Rgb {
+        let random_generator = &mut RandomGenerator::new(3);
+        let mut sum = Rgb::new(0.0, 0.0, 0.0);
+        for _ in 0..samples {
+            sum = sum + integrator.radiance(ray, scene, random_generator);
+        }
+        sum / samples
+    }
+
+    #[test]
+    fn test_by_name() {
+        for name in INTEGRATOR_NAMES {
+            assert!(by_name(name).is_ok());
+        }
+        assert!(by_name("photon_mapping").is_err());
+    }
+
+    // a white floor under a white sky reflects half of it with an albedo of 0.5, whatever
+    // the way the light is found
+    #[test]
+    fn test_furnace() {
+        let world: HittableList = vec![Arc::new(Quad::new(
+            Point::new(-1000.0, 0.0, -1000.0),
+            Vec3::new(0.0, 0.0, 2000.0),
+            Vec3::new(2000.0, 0.0, 0.0),
+            Arc::new(Lambertian::new(Rgb::new(0.5, 0.5, 0.5))),
+        ))];
+        let lights = HittableList::new();
+        let sky = Rgb::white();
+        let scene = view(&world, &lights, &sky);
+        let ray = Ray::new(Point::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        for integrator in [
+            Arc::new(PathTracer::default()) as Arc<dyn Integrator>,
+            Arc::new(Whitted),
+        ] {
+            let color = average(integrator.as_ref(), &scene, &ray, 4000);
+            assert!((color.g - 0.5).abs() < 0.02, "{:?}", color);
+        }
+    }
+
+    // a small lamp over a floor, the light sampling strategies all converge to the same value
+    #[test]
+    fn test_light_sampling() {
+        let lamp = Arc::new(Sphere::new(
+            Point::new(0.0, 2.0, 0.0),
+            0.2,
+            Arc::new(DiffuseLight {
+                emit: Rgb::new(10.0, 10.0, 10.0),
+            }),
+        ));
+        let world: HittableList = vec![
+            Arc::new(Quad::new(
+                Point::new(-10.0, 0.0, -10.0),
+                Vec3::new(0.0, 0.0, 20.0),
+                Vec3::new(20.0, 0.0, 0.0),
+                Arc::new(Lambertian::new(Rgb::new(0.5, 0.5, 0.5))),
+            )),
+            lamp.clone(),
+        ];
+        let lights: HittableList = vec![lamp];
+        let black = Rgb::new(0.0, 0.0, 0.0);
+        let scene = view(&world, &lights, &black);
+        let ray = Ray::new(Point::new(0.5, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        let reference = average(&PathTracer::default(), &scene, &ray, 20000).g;
+        for light_sampling in [LightSampling::Mixture, LightSampling::Balance] {
+            let color = average(&PathTracer { light_sampling }, &scene, &ray, 20000);
+            assert!(
+                (color.g - reference).abs() < 0.03 * reference,
+                "{:?}",
+                color
+            );
+        }
+        let direct = average(&Whitted, &scene, &ray, 20000);
+        assert!((direct.g - reference).abs() < 0.03 * reference);
+    }
+
+    // an emitter left out of `lights`, like an obj mesh with `Ke`, in front of a sampled lamp:
+    // the light samples must see its glow where the bounces that hit it lose weight
+    #[test]
+    fn test_emitter_in_front_of_light() {
+        let lamp = Arc::new(Quad::new(
+            Point::new(-0.5, 2.0, -0.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Arc::new(DiffuseLight {
+                emit: Rgb::new(10.0, 10.0, 10.0),
+            }),
+        ));
+        let glow = Arc::new(Quad::new(
+            Point::new(-0.25, 1.5, -0.25),
+            Vec3::new(0.5, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 0.5),
+            Arc::new(DiffuseLight {
+                emit: Rgb::new(4.0, 4.0, 4.0),
+            }),
+        ));
+        let world: HittableList = vec![
+            Arc::new(Quad::new(
+                Point::new(-10.0, 0.0, -10.0),
+                Vec3::new(0.0, 0.0, 20.0),
+                Vec3::new(20.0, 0.0, 0.0),
+                Arc::new(Lambertian::new(Rgb::new(0.5, 0.5, 0.5))),
+            )),
+            lamp.clone(),
+            glow,
+        ];
+        let lights: HittableList = vec![lamp];
+        let black = Rgb::new(0.0, 0.0, 0.0);
+        let scene = view(&world, &lights, &black);
+        let ray = Ray::new(Point::new(0.1, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        let reference = average(
+            &PathTracer {
+                light_sampling: LightSampling::Mixture,
+            },
+            &scene,
+            &ray,
+            40000,
+        )
+        .g;
+        for light_sampling in [LightSampling::Power, LightSampling::Balance] {
+            let color = average(&PathTracer { light_sampling }, &scene, &ray, 40000);
+            assert!(
+                (color.g - reference).abs() < 0.03 * reference,
+                "{:?} {}",
+                color,
+                reference
+            );
+        }
+        let direct = average(&Whitted, &scene, &ray, 40000);
+        assert!((direct.g - reference).abs() < 0.03 * reference);
+    }
+
+    // without light sampling a big lamp is still found by chance, only with more noise
+    #[test]
+    fn test_no_light_sampling() {
+        let lamp = Arc::new(Sphere::new(
+            Point::new(0.0, 3.0, 0.0),
+            1.0,
+            Arc::new(DiffuseLight {
+                emit: Rgb::new(4.0, 4.0, 4.0),
+            }),
+        ));
+        let world: HittableList = vec![
+            Arc::new(Quad::new(
+                Point::new(-10.0, 0.0, -10.0),
+                Vec3::new(0.0, 0.0, 20.0),
+                Vec3::new(20.0, 0.0, 0.0),
+                Arc::new(Lambertian::new(Rgb::new(0.5, 0.5, 0.5))),
+            )),
+            lamp.clone(),
+        ];
+        let lights: HittableList = vec![lamp];
+        let black = Rgb::new(0.0, 0.0, 0.0);
+        let scene = view(&world, &lights, &black);
+        let ray = Ray::new(Point::new(0.5, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        let reference = average(&PathTracer::default(), &scene, &ray, 20000).g;
+        let naive = PathTracer {
+            light_sampling: LightSampling::None,
+        };
+        let color = average(&naive, &scene, &ray, 100000);
+        assert!(
+            (color.g - reference).abs() < 0.03 * reference,
+            "{:?} {}",
+            color,
+            reference
+        );
+    }
+
+    // diffuse and glowing, from the inside of a sphere of it every bounce finds the glow again
+    struct Glowing;
+
+    impl Material for Glowing {
+        fn scatter(&self, _: &Ray, hit: &HitRecord, _: &mut RandomGenerator) -> Option<Scatter> {
+            Some(Scatter::Diffuse {
+                pdf: Box::new(CosinePdf::new(hit.normal)),
+            })
+        }
+
+        fn scattering(&self, _: &Ray, hit: &HitRecord, scattered: &Ray) -> Rgb {
+            Rgb::new(0.8, 0.8, 0.8) * (hit.normal.dot(scattered.dir().unit_vector()).max(0.0) / PI)
+        }
+
+        fn emitted(&self, _: &HitRecord) -> Rgb {
+            Rgb::white()
+        }
+    }
+
+    // the light of every bounce adds up to 1 / (1 - 0.8), paths long enough to get there
+    // are ended by the roulette long before `max_depth`
+    #[test]
+    fn test_russian_roulette() {
+        let world: HittableList = vec![Arc::new(Sphere::new(
+            Point::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Glowing),
+        ))];
+        let lights = HittableList::new();
+        let black = Rgb::new(0.0, 0.0, 0.0);
+        let mut scene = view(&world, &lights, &black);
+        scene.max_depth = 10000;
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.3, 0.1, -1.0));
+
+        for light_sampling in [LightSampling::Mixture, LightSampling::Power] {
+            let color = average(&PathTracer { light_sampling }, &scene, &ray, 20000);
+            assert!((color.g - 5.0).abs() < 0.1, "{:?}", color);
+        }
+    }
+
+    #[test]
+    fn test_debug_integrators() {
+        let world: HittableList = vec![Arc::new(Sphere::new(
+            Point::new(0.0, 0.0, -3.0),
+            1.0,
+            Arc::new(Lambertian::new(Rgb::new(0.2, 0.4, 0.6))),
+        ))];
+        let lights = HittableList::new();
+        let black = Rgb::new(0.0, 0.0, 0.0);
+        let scene = view(&world, &lights, &black);
+        let random_generator = &mut RandomGenerator::new(0);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let miss = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+
+        let normal = Normals.radiance(&ray, &scene, random_generator);
+        assert!(normal.r == 0.5 && normal.g == 0.5 && normal.b == 1.0);
+        let depth = Depth { max_distance: 4.0 }.radiance(&ray, &scene, random_generator);
+        assert!((depth.r - 0.5).abs() < 1e-9);
+        let albedo = Albedo.radiance(&ray, &scene, random_generator);
+        assert!(albedo.r == 0.2 && albedo.g == 0.4 && albedo.b == 0.6);
+        // nothing around the sphere to block the rays leaving it
+        let occlusion = AmbientOcclusion::default().radiance(&ray, &scene, random_generator);
+        assert!(occlusion.r == 1.0);
+        assert!(Depth::default().radiance(&miss, &scene, random_generator).r == 0.0);
+    }
+}
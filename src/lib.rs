@@ -1,6 +1,21 @@
-pub mod hittable;
+pub mod aabb;
+pub mod background;
+pub mod bvh;
 pub mod camera;
+pub mod distribution;
+pub mod environment;
+pub mod framebuffer;
+pub mod hittable;
+pub mod integrator;
 pub mod material;
+pub mod medium;
+pub mod microfacet;
+pub mod obj;
+pub mod pdf;
+pub mod random;
 pub mod ray;
+pub mod scene;
 pub mod shape;
+pub mod texture;
+pub mod tonemap;
 pub mod vec3;
@@ -1,126 +1,350 @@
-use rand::Rng;
+use clap::Parser;
 use rtoneweekend::{
-    camera::{Camera, CameraConfig},
-    hittable::HittableList,
-    material::{Dieletric, Lambertian, Metal, Rgb},
-    shape::Sphere,
-    vec3::{Point, Vec3},
+    bvh::BvhNode,
+    camera::Camera,
+    integrator::{self, Integrator, LightSampling, PathTracer},
+    scene::{self, Scene},
+    tonemap::ToneMapper,
+    vec3::Vec3,
 };
-use std::rc::Rc;
+use std::{fmt::Display, mem, path::Path, process, str::FromStr, sync::Arc};
 
-fn main() {
-    let mut camera: Camera = Camera::create(CameraConfig {
-        width: 2560,
-        vfov: 20.0,
-        max_depth: 20,
-        samples_per_pixel: 100,
-        look_from: Point::new(13.0, 2.0, 3.0),
-        look_at: Point::new(0.0, 0.0, 0.0),
-        camera_vup: Vec3::new(0.0, 1.0, 0.0),
-        float_correction: 0.00001,
-        defocus_angle: 0.6,
-        focus_dist: 10.0,
-        save_path: "/tmp/pic.png",
-        aspect_ratio: 16.0 / 9.0,
-        ..Default::default()
-    });
-    // dbg!(&camera);
+/// Path traces a scene and saves it as an image.
+#[derive(Parser)]
+#[command(
+    version,
+    about,
+    after_help = "Exits with 2 when the flags, the scene file or the camera settings are \
+                  invalid, and with 1 when the image can't be saved."
+)]
+struct Cli {
+    /// Name of a built-in scene or path to a TOML scene file
+    #[arg(default_value = "random_spheres")]
+    scene: String,
 
-    let mut random_generator = rand::thread_rng();
+    /// Where to save the image, overrides the scene's save_path
+    #[arg(short, long)]
+    output: Option<String>,
 
-    // world
-    let mut world: HittableList = HittableList::new();
-    let ground_material = Rc::new(Lambertian {
-        albedo: Rgb::new(0.5, 0.5, 0.5),
-    });
-    let ground = Rc::new(Sphere {
-        center: Point::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: ground_material.clone(),
-    });
-    world.push(ground.clone());
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = random_generator.gen_range(0.0..1.0);
-            let center = Point::new(
-                a as f64 + 0.9 * random_generator.gen_range(0.0..1.0),
-                0.2,
-                b as f64 + 0.9 * random_generator.gen_range(0.0..1.0),
-            );
-
-            if (center - Point::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                match choose_mat {
-                    a if a < 0.8 => {
-                        let albedo = Rgb::new(
-                            random_generator.gen_range(0.0..1.0),
-                            random_generator.gen_range(0.0..1.0),
-                            random_generator.gen_range(0.0..1.0),
-                        ) * Rgb::new(
-                            random_generator.gen_range(0.0..1.0),
-                            random_generator.gen_range(0.0..1.0),
-                            random_generator.gen_range(0.0..1.0),
-                        );
-                        let sphere_material = Rc::new(Lambertian { albedo });
-                        let rand_sphere = Rc::new(Sphere {
-                            center,
-                            radius: 0.2,
-                            material: sphere_material.clone(),
-                        });
-                        world.push(rand_sphere.clone());
-                    }
-                    b if b < 0.95 => {
-                        let albedo = Rgb::new(
-                            random_generator.gen_range(0.5..1.0),
-                            random_generator.gen_range(0.5..1.0),
-                            random_generator.gen_range(0.5..1.0),
-                        );
-                        let fuzz = random_generator.gen_range(0.0..0.5);
-                        let sphere_material = Rc::new(Metal { albedo, fuzz });
-                        let rand_sphere = Rc::new(Sphere {
-                            center,
-                            radius: 0.2,
-                            material: sphere_material.clone(),
-                        });
-                        world.push(rand_sphere.clone());
-                    }
-                    _ => {
-                        let sphere_meterial = Rc::new(Dieletric { ir: 1.5 });
-                        let rand_sphere = Rc::new(Sphere {
-                            center,
-                            radius: 0.2,
-                            material: sphere_meterial.clone(),
-                        });
-                        world.push(rand_sphere.clone());
-                    }
-                }
-            }
+    /// Also save the linear render to this .exr or .hdr file
+    #[arg(long)]
+    hdr_output: Option<String>,
+
+    /// Exposure of the 8-bit output in stops
+    #[arg(long, allow_hyphen_values = true)]
+    exposure: Option<f64>,
+
+    /// Tone mapping curve of the 8-bit output: aces (the default), reinhard, reinhard_extended,
+    /// hable, or clamp to clip the highlights
+    #[arg(long, value_parser = ToneMapper::from_str)]
+    tone_mapper: Option<ToneMapper>,
+
+    /// Luminance mapped to white by reinhard_extended
+    #[arg(long, value_parser = positive::<f64>)]
+    white_point: Option<f64>,
+
+    /// Hide the progress bar and messages
+    #[arg(short, long)]
+    quiet: bool,
+
+    #[arg(long, value_parser = positive::<f64>)]
+    aspect_ratio: Option<f64>,
+
+    /// Image width in pixels
+    #[arg(long, value_parser = positive::<usize>)]
+    width: Option<usize>,
+
+    #[arg(long, visible_alias = "spp", value_parser = positive::<usize>)]
+    samples_per_pixel: Option<usize>,
+
+    /// Most bounces of a path, a safety cap: paths carrying little light end before at random
+    #[arg(long, value_parser = positive::<usize>)]
+    max_depth: Option<usize>,
+
+    #[arg(long, value_parser = positive::<f64>)]
+    float_correction: Option<f64>,
+
+    /// Vertical field of view in degrees
+    #[arg(long, value_parser = parse_vfov)]
+    vfov: Option<f64>,
+
+    /// Camera position as x,y,z
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    look_from: Option<Vec3>,
+
+    /// Point the camera looks at as x,y,z
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    look_at: Option<Vec3>,
+
+    /// Camera up direction as x,y,z
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    camera_vup: Option<Vec3>,
+
+    /// Aperture angle in degrees, 0 disables depth of field
+    #[arg(long, value_parser = non_negative)]
+    defocus_angle: Option<f64>,
+
+    #[arg(long, value_parser = positive::<f64>)]
+    focus_dist: Option<f64>,
+
+    /// Time when the shutter opens, moving shapes start at time 0
+    #[arg(long, allow_hyphen_values = true)]
+    shutter_open: Option<f64>,
+
+    /// Time when the shutter closes, a later time gives a longer motion blur
+    #[arg(long, allow_hyphen_values = true)]
+    shutter_close: Option<f64>,
+
+    /// How the camera rays are turned into colors: path, whitted, or the debug views normals,
+    /// depth, albedo and ambient_occlusion, with their default settings
+    #[arg(long, value_parser = integrator::by_name)]
+    integrator: Option<Arc<dyn Integrator>>,
+
+    /// Path traces with this way for diffuse bounces to find the lights: none (only by chance),
+    /// mixture, balance or power
+    #[arg(long, value_parser = LightSampling::from_str, conflicts_with = "integrator")]
+    light_sampling: Option<LightSampling>,
+
+    /// Render threads, 0 uses every core
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+
+    /// Seed of the camera's sampling, the same seed and scene give the same image. It doesn't
+    /// change the layout of the built-in scenes, their random spheres always use seed 2023
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+fn positive<T: FromStr + PartialOrd + Default + Display>(s: &str) -> Result<T, String> {
+    let value: T = s.parse().map_err(|_| format!("`{}` isn't a number", s))?;
+    if value > T::default() {
+        Ok(value)
+    } else {
+        Err(format!("must be greater than 0, got {}", value))
+    }
+}
+
+fn non_negative(s: &str) -> Result<f64, String> {
+    let value: f64 = s.parse().map_err(|_| format!("`{}` isn't a number", s))?;
+    if value >= 0.0 {
+        Ok(value)
+    } else {
+        Err(format!("must not be negative, got {}", value))
+    }
+}
+
+fn parse_vfov(s: &str) -> Result<f64, String> {
+    let value = positive::<f64>(s)?;
+    if value < 180.0 {
+        Ok(value)
+    } else {
+        Err(format!("must be below 180 degrees, got {}", value))
+    }
+}
+
+fn parse_vec3(s: &str) -> Result<Vec3, String> {
+    let parts: Vec<f64> = s
+        .split(',')
+        .map(|part| part.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("`{}` isn't a list of numbers", s))?;
+    match parts[..] {
+        [i, j, k] => Ok(Vec3::new(i, j, k)),
+        _ => Err(format!(
+            "expected 3 comma separated numbers, got {}",
+            parts.len()
+        )),
+    }
+}
+
+impl Cli {
+    fn load_scene(&self) -> Result<Scene, String> {
+        if let Some(scene) = scene::builtin(&self.scene) {
+            return Ok(scene);
         }
+        if !Path::new(&self.scene).exists() {
+            return Err(format!(
+                "no scene file `{}` and no built-in scene with that name (built-in: {})",
+                self.scene,
+                scene::BUILTIN_SCENES.join(", ")
+            ));
+        }
+        Scene::load(&self.scene).map_err(|err| err.to_string())
     }
 
-    let material1 = Rc::new(Dieletric { ir: 1.5 });
-    world.push(Rc::new(Sphere {
-        center: Point::new(0.0, 1.0, 0.0),
-        radius: 1.0,
-        material: material1.clone(),
-    }));
-    let material2 = Rc::new(Lambertian {
-        albedo: Rgb::new(0.4, 0.2, 0.1),
-    });
-    world.push(Rc::new(Sphere {
-        center: Point::new(-5.0, 1.0, 0.0),
-        radius: 1.0,
-        material: material2.clone(),
-    }));
-    let material3 = Rc::new(Metal {
-        albedo: Rgb::new(0.7, 0.6, 0.5),
-        fuzz: 0.0,
+    fn apply_overrides(&self, scene: &mut Scene) {
+        let camera = &mut scene.camera;
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            camera.aspect_ratio = aspect_ratio;
+        }
+        if let Some(width) = self.width {
+            camera.width = width;
+        }
+        if let Some(samples_per_pixel) = self.samples_per_pixel {
+            camera.samples_per_pixel = samples_per_pixel;
+        }
+        if let Some(max_depth) = self.max_depth {
+            camera.max_depth = max_depth;
+        }
+        if let Some(float_correction) = self.float_correction {
+            camera.float_correction = float_correction;
+        }
+        if let Some(vfov) = self.vfov {
+            camera.vfov = vfov;
+        }
+        if let Some(look_from) = self.look_from {
+            camera.look_from = look_from;
+        }
+        if let Some(look_at) = self.look_at {
+            camera.look_at = look_at;
+        }
+        if let Some(camera_vup) = self.camera_vup {
+            camera.camera_vup = camera_vup;
+        }
+        if let Some(defocus_angle) = self.defocus_angle {
+            camera.defocus_angle = defocus_angle;
+        }
+        if let Some(focus_dist) = self.focus_dist {
+            camera.focus_dist = focus_dist;
+        }
+        if let Some(shutter_open) = self.shutter_open {
+            camera.shutter_open = shutter_open;
+        }
+        if let Some(shutter_close) = self.shutter_close {
+            camera.shutter_close = shutter_close;
+        }
+        if let Some(integrator) = &self.integrator {
+            camera.integrator = integrator.clone();
+        }
+        if let Some(light_sampling) = self.light_sampling {
+            camera.integrator = Arc::new(PathTracer { light_sampling });
+        }
+        if let Some(threads) = self.threads {
+            camera.threads = threads;
+        }
+        if let Some(seed) = self.seed {
+            camera.seed = seed;
+        }
+        if let Some(exposure) = self.exposure {
+            camera.tone_mapping.exposure = exposure;
+        }
+        if let Some(tone_mapper) = self.tone_mapper {
+            camera.tone_mapping.tone_mapper = tone_mapper;
+        }
+        if let Some(white_point) = self.white_point {
+            camera.tone_mapping.white_point = white_point;
+        }
+        camera.quiet = self.quiet;
+        if let Some(output) = &self.output {
+            scene.save_path = output.clone();
+        }
+        if let Some(hdr_output) = &self.hdr_output {
+            scene.hdr_save_path = Some(hdr_output.clone());
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let mut scene = cli.load_scene().unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(2);
     });
-    world.push(Rc::new(Sphere {
-        center: Point::new(4.0, 1.0, 0.0),
-        radius: 1.0,
-        material: material3.clone(),
-    }));
+    cli.apply_overrides(&mut scene);
+    // the flags are checked one by one, this catches combinations like look_at == look_from
+    if let Err(err) = scene::validate_camera(&scene.camera_config()) {
+        eprintln!("error: {}", err);
+        process::exit(2);
+    }
+
+    let world = BvhNode::new(mem::take(&mut scene.world));
+    let mut camera: Camera = Camera::create(scene.camera_config());
+    if let Err(err) = camera.render(&world, &scene.lights) {
+        eprintln!("error: can't save the image: {}", err);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positive() {
+        assert!(positive::<usize>("3") == Ok(3));
+        assert!(positive::<f64>("0.5") == Ok(0.5));
+        assert!(positive::<usize>("0") == Err("must be greater than 0, got 0".to_string()));
+        assert!(positive::<f64>("-1") == Err("must be greater than 0, got -1".to_string()));
+        assert!(positive::<usize>("many") == Err("`many` isn't a number".to_string()));
+    }
+
+    #[test]
+    fn test_non_negative() {
+        assert!(non_negative("0") == Ok(0.0));
+        assert!(non_negative("-0.5") == Err("must not be negative, got -0.5".to_string()));
+        assert!(non_negative("wide") == Err("`wide` isn't a number".to_string()));
+    }
 
-    camera.render(&world, &mut random_generator);
+    #[test]
+    fn test_parse_vfov() {
+        assert!(parse_vfov("90") == Ok(90.0));
+        assert!(parse_vfov("180") == Err("must be below 180 degrees, got 180".to_string()));
+        assert!(parse_vfov("0") == Err("must be greater than 0, got 0".to_string()));
+    }
+
+    #[test]
+    fn test_parse_vec3() {
+        let v = parse_vec3("1, -2,3.5").unwrap();
+
+        assert!(v.i == 1.0 && v.j == -2.0 && v.k == 3.5);
+        assert!(
+            parse_vec3("1,2").err()
+                == Some("expected 3 comma separated numbers, got 2".to_string())
+        );
+        assert!(parse_vec3("1,y,3").err() == Some("`1,y,3` isn't a list of numbers".to_string()));
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let cli = Cli::try_parse_from([
+            "rtoneweekend",
+            "scene.toml",
+            "--width",
+            "64",
+            "--spp",
+            "7",
+            "--look-from",
+            "-1,2,3",
+            "--seed",
+            "9",
+            "--output",
+            "/tmp/out.png",
+            "--quiet",
+        ])
+        .unwrap();
+        let mut scene =
+            Scene::parse("[camera]\nwidth = 400\nmax_depth = 8", Path::new(".")).unwrap();
+        cli.apply_overrides(&mut scene);
+
+        assert!(scene.camera.width == 64 && scene.camera.samples_per_pixel == 7);
+        assert!(scene.camera.look_from.i == -1.0 && scene.camera.look_from.k == 3.0);
+        assert!(scene.camera.seed == 9 && scene.camera.quiet);
+        // the flags left out keep the scene's values
+        assert!(scene.camera.max_depth == 8);
+        assert!(scene.save_path == "/tmp/out.png");
+    }
+
+    #[test]
+    fn test_rejected_flags() {
+        for args in [
+            ["--max-depth", "0"],
+            ["--vfov", "200"],
+            ["--look-at", "1,2"],
+            ["--defocus-angle", "-1"],
+        ] {
+            let cli = Cli::try_parse_from(["rtoneweekend", args[0], args[1]]);
+            assert!(cli.is_err(), "{:?}", args);
+        }
+    }
 }
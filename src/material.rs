@@ -1,8 +1,23 @@
-use std::ops::{Add, Div, Mul, Sub};
-
-use rand::{rngs::ThreadRng, Rng};
-
-use crate::{hittable::HitRecord, ray::Ray, vec3::Vec3};
+use std::{
+    f64::consts::PI,
+    ops::{Add, Div, Mul, Sub},
+    sync::Arc,
+};
+
+use rand::Rng;
+
+use crate::{
+    hittable::HitRecord,
+    microfacet::{
+        fresnel_conductor_rgb, fresnel_dielectric, refraction_half_vector, Frame, Ggx,
+        GgxDielectricPdf, GgxReflectionPdf,
+    },
+    pdf::{CosinePdf, Pdf, SpherePdf},
+    random::RandomGenerator,
+    ray::Ray,
+    texture::Texture,
+    vec3::Vec3,
+};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Rgb {
@@ -24,13 +39,32 @@ impl Rgb {
         }
     }
 
-    pub fn to_gamma(self) -> Rgb {
+    // the sRGB transfer curve, the input is clamped to [0, 1] first
+    pub fn to_srgb(self) -> Rgb {
+        fn encode(c: f64) -> f64 {
+            let c = c.clamp(0.0, 1.0);
+            if c <= 0.0031308 {
+                12.92 * c
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        }
+
         Rgb {
-            r: self.r.sqrt(),
-            g: self.g.sqrt(),
-            b: self.b.sqrt(),
+            r: encode(self.r),
+            g: encode(self.g),
+            b: encode(self.b),
         }
     }
+
+    pub fn max_component(&self) -> f64 {
+        self.r.max(self.g).max(self.b)
+    }
+
+    // relative luminance with the Rec. 709 primaries
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
 }
 
 impl Mul<f64> for Rgb {
@@ -105,73 +139,285 @@ impl Div<usize> for Rgb {
     }
 }
 
-/// The trait represents the material of the shape. It will return the scattered ray and its color.
-pub trait Material {
+/// What happens to a ray hitting a material.
+pub enum Scatter {
+    /// A single outgoing ray, like a mirror reflection, there's nothing to importance sample.
+    Specular { ray: Ray, attenuation: Rgb },
+    /// The light leaves in a spread of directions, wide for matte surfaces or narrow for rough
+    /// metals and glass: the path tracer picks one from `pdf`, possibly along with directions
+    /// toward the lights, and weights it with `Material::scattering`.
+    Diffuse { pdf: Box<dyn Pdf> },
+}
+
+/// The trait represents the material of the shape. It will return how the ray scatters, or
+/// `None` when the ray is absorbed, plus the light the surface emits by itself.
+pub trait Material: Send + Sync {
     fn scatter(
         &self,
         ray_in: &Ray,
         hit_record: &HitRecord,
-        random_generator: &mut ThreadRng,
-    ) -> (Ray, Rgb);
+        random_generator: &mut RandomGenerator,
+    ) -> Option<Scatter>;
+
+    // the fraction of the light coming back along `scattered` that leaves along the reverse of
+    // `ray_in`, per steradian and with the cosine at the surface: the BSDF times the cosine.
+    // Only needed by materials returning `Scatter::Diffuse`.
+    fn scattering(&self, _ray_in: &Ray, _hit_record: &HitRecord, _scattered: &Ray) -> Rgb {
+        Rgb::new(0.0, 0.0, 0.0)
+    }
+
+    fn emitted(&self, _hit_record: &HitRecord) -> Rgb {
+        Rgb::new(0.0, 0.0, 0.0)
+    }
 }
 
 pub struct Lambertian {
-    pub albedo: Rgb,
+    pub albedo: Arc<dyn Texture>,
+}
+
+impl Lambertian {
+    // `albedo` is an `Rgb` for a plain color
+    pub fn new(albedo: impl Texture + 'static) -> Lambertian {
+        Lambertian {
+            albedo: Arc::new(albedo),
+        }
+    }
 }
 
 impl Material for Lambertian {
-    fn scatter(
-        &self,
-        _: &Ray,
-        hit_record: &HitRecord,
-        random_generator: &mut ThreadRng,
-    ) -> (Ray, Rgb) {
-        let scatter_direction = hit_record.normal + Vec3::random_unit_vector(random_generator);
+    fn scatter(&self, _: &Ray, hit_record: &HitRecord, _: &mut RandomGenerator) -> Option<Scatter> {
+        Some(Scatter::Diffuse {
+            pdf: Box::new(CosinePdf::new(hit_record.normal)),
+        })
+    }
 
-        (
-            Ray {
-                origin: hit_record.intersection,
-                direction: scatter_direction,
-            },
-            self.albedo,
-        )
+    fn scattering(&self, _: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Rgb {
+        let cos_theta = hit_record.normal.dot(scattered.dir().unit_vector());
+        self.albedo
+            .value(hit_record.u, hit_record.v, hit_record.intersection)
+            * (cos_theta.max(0.0) / PI)
     }
 }
 
 pub struct Metal {
-    pub albedo: Rgb,
+    pub albedo: Arc<dyn Texture>,
     pub fuzz: f64,
 }
 
+impl Metal {
+    pub fn new(albedo: impl Texture + 'static, fuzz: f64) -> Metal {
+        Metal {
+            albedo: Arc::new(albedo),
+            fuzz,
+        }
+    }
+
+    fn fuzz_pdf(&self, ray_in: &Ray, hit_record: &HitRecord) -> FuzzPdf {
+        FuzzPdf {
+            reflected: ray_in.dir().unit_vector().reflect(hit_record.normal),
+            fuzz: self.fuzz.clamp(0.0, 1.0),
+        }
+    }
+}
+
 impl Material for Metal {
     fn scatter(
         &self,
         ray_in: &Ray,
         hit_record: &HitRecord,
-        random_generator: &mut ThreadRng,
-    ) -> (Ray, Rgb) {
-        let reflect = ray_in.dir().unit_vector().reflect(hit_record.normal);
-        let fuzz_factor = self.fuzz.clamp(0.0, 1.0);
+        _: &mut RandomGenerator,
+    ) -> Option<Scatter> {
+        let pdf = self.fuzz_pdf(ray_in, hit_record);
+
+        // a perfect mirror has a single direction, no light sample could ever land on it
+        if pdf.fuzz <= 0.0 {
+            return Some(Scatter::Specular {
+                ray: Ray {
+                    origin: hit_record.intersection,
+                    direction: pdf.reflected,
+                    time: ray_in.time(),
+                },
+                attenuation: self
+                    .albedo
+                    .value(hit_record.u, hit_record.v, hit_record.intersection),
+            });
+        }
+        Some(Scatter::Diffuse { pdf: Box::new(pdf) })
+    }
 
-        (
-            Ray {
-                origin: hit_record.intersection,
-                direction: reflect + fuzz_factor * Vec3::random_unit_vector(random_generator),
-            },
-            self.albedo,
-        )
+    // the fuzzy reflection is defined by the way it's sampled, so it follows its own density
+    fn scattering(&self, ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Rgb {
+        self.albedo
+            .value(hit_record.u, hit_record.v, hit_record.intersection)
+            * self.fuzz_pdf(ray_in, hit_record).value(scattered.dir())
     }
 }
 
+// the directions toward a random point of the sphere of radius `fuzz` around the tip of the
+// unit `reflected` direction
+struct FuzzPdf {
+    reflected: Vec3,
+    fuzz: f64,
+}
+
+impl Pdf for FuzzPdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        // the points t * w on the sphere, and the solid angle each covers
+        let w = direction.unit_vector();
+        let b = w.dot(self.reflected);
+        let discriminant = b * b - (1.0 - self.fuzz * self.fuzz);
+        if discriminant < 0.0 {
+            return 0.0;
+        }
+
+        let area_pdf = 1.0 / (4.0 * PI * self.fuzz * self.fuzz);
+        let sqrt_d = discriminant.sqrt();
+        [b - sqrt_d, b + sqrt_d]
+            .into_iter()
+            .filter(|t| *t > 0.0)
+            .map(|t| {
+                let normal = (t * w - self.reflected) / self.fuzz;
+                let cosine = w.dot(normal).abs();
+                match cosine > 0.0 {
+                    true => area_pdf * t * t / cosine,
+                    false => 0.0,
+                }
+            })
+            .sum()
+    }
+
+    fn generate(&self, random_generator: &mut RandomGenerator) -> Vec3 {
+        self.reflected + self.fuzz * Vec3::random_unit_vector(random_generator)
+    }
+}
+
+// below this roughness the highlight is narrower than a pixel, it's rendered as a mirror
+const SMOOTH_ROUGHNESS: f64 = 0.02;
+
+/// Names of the metals with a `Conductor::preset`.
+pub const METAL_NAMES: [&str; 4] = ["gold", "copper", "aluminum", "silver"];
+
+/// A rough metal made of GGX microfacets. Its color comes from the complex index of refraction
+/// `eta + i k` of each channel, `roughness` goes from a mirror at 0 to a matte look at 1.
+#[derive(Clone, Copy, Debug)]
+pub struct Conductor {
+    pub eta: Rgb,
+    pub k: Rgb,
+    pub roughness: f64,
+}
+
+impl Conductor {
+    pub fn new(eta: Rgb, k: Rgb, roughness: f64) -> Conductor {
+        Conductor { eta, k, roughness }
+    }
+
+    /// A metal of `METAL_NAMES`, measured at the wavelengths of the red, green and blue
+    /// channels.
+    pub fn preset(name: &str, roughness: f64) -> Option<Conductor> {
+        let (eta, k) = match name {
+            "gold" => (
+                Rgb::new(0.143119, 0.374957, 1.44248),
+                Rgb::new(3.98316, 2.38572, 1.60322),
+            ),
+            "copper" => (
+                Rgb::new(0.200438, 0.924033, 1.10221),
+                Rgb::new(3.91295, 2.45285, 2.14219),
+            ),
+            "aluminum" => (
+                Rgb::new(1.65746, 0.880369, 0.521229),
+                Rgb::new(9.22387, 6.26952, 4.837),
+            ),
+            "silver" => (
+                Rgb::new(0.155265, 0.116723, 0.138342),
+                Rgb::new(4.82835, 3.12225, 2.14696),
+            ),
+            _ => return None,
+        };
+        Some(Conductor::new(eta, k, roughness))
+    }
+
+    fn ggx(&self) -> Ggx {
+        Ggx::from_roughness(self.roughness.clamp(0.0, 1.0))
+    }
+}
+
+// `wo` leaves the surface toward where the ray came from, in the normal's frame
+fn local_wo(ray_in: &Ray, hit_record: &HitRecord) -> (Frame, Vec3) {
+    let frame = Frame::new(hit_record.normal);
+    let wo = frame.to_local(-ray_in.dir().unit_vector());
+    (frame, wo)
+}
+
+impl Material for Conductor {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        _: &mut RandomGenerator,
+    ) -> Option<Scatter> {
+        let (frame, wo) = local_wo(ray_in, hit_record);
+        if wo.k <= 0.0 {
+            return None;
+        }
+
+        if self.roughness < SMOOTH_ROUGHNESS {
+            return Some(Scatter::Specular {
+                ray: Ray {
+                    origin: hit_record.intersection,
+                    direction: ray_in.dir().unit_vector().reflect(hit_record.normal),
+                    time: ray_in.time(),
+                },
+                attenuation: fresnel_conductor_rgb(wo.k, self.eta, self.k),
+            });
+        }
+        Some(Scatter::Diffuse {
+            pdf: Box::new(GgxReflectionPdf {
+                frame,
+                wo,
+                ggx: self.ggx(),
+            }),
+        })
+    }
+
+    fn scattering(&self, ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Rgb {
+        let (frame, wo) = local_wo(ray_in, hit_record);
+        let wi = frame.to_local(scattered.dir().unit_vector());
+        if wo.k <= 0.0 || wi.k <= 0.0 {
+            return Rgb::new(0.0, 0.0, 0.0);
+        }
+
+        // F D G / (4 cos_o cos_i), the cos_i goes away with the cosine at the surface
+        let ggx = self.ggx();
+        let m = (wo + wi).unit_vector();
+        fresnel_conductor_rgb(wo.dot(m), self.eta, self.k)
+            * (ggx.d(m) * ggx.g(wo, wi) / (4.0 * wo.k))
+    }
+}
+
+/// Glass, water and other transparent materials, reflecting or refracting the rays by the
+/// Fresnel equations. A `roughness` above 0 spreads both with GGX microfacets, like frosted
+/// glass or ice.
 pub struct Dieletric {
-    pub ir: f64, // index of refraction
+    pub ir: f64,        // index of refraction
+    pub roughness: f64, // 0 for polished surfaces, up to 1
 }
 
 impl Dieletric {
-    fn reflectance(ref_ix: f64, cos_theta: f64) -> f64 {
-        let mut r0 = (1.0 - ref_ix) / (1.0 + ref_ix);
-        r0 = r0 * r0;
-        r0 + (1.0 - r0) * (1.0 - cos_theta).powi(5)
+    pub fn new(ir: f64, roughness: f64) -> Dieletric {
+        Dieletric { ir, roughness }
+    }
+
+    // index of refraction on the far side of the surface over the one on the side of the ray
+    fn eta(&self, hit_record: &HitRecord) -> f64 {
+        if hit_record.out_facing {
+            self.ir
+        } else {
+            1.0 / self.ir
+        }
+    }
+
+    fn ggx(&self) -> Ggx {
+        Ggx::from_roughness(self.roughness.clamp(0.0, 1.0))
     }
 }
 
@@ -180,32 +426,120 @@ impl Material for Dieletric {
         &self,
         ray_in: &Ray,
         hit_record: &HitRecord,
-        random_generator: &mut ThreadRng,
-    ) -> (Ray, Rgb) {
-        let ir = if hit_record.out_facing {
-            1.0 / self.ir
-        } else {
-            self.ir
-        };
+        random_generator: &mut RandomGenerator,
+    ) -> Option<Scatter> {
+        let eta = self.eta(hit_record);
+
+        if self.roughness >= SMOOTH_ROUGHNESS {
+            let (frame, wo) = local_wo(ray_in, hit_record);
+            if wo.k <= 0.0 {
+                return None;
+            }
+            return Some(Scatter::Diffuse {
+                pdf: Box::new(GgxDielectricPdf {
+                    frame,
+                    wo,
+                    ggx: self.ggx(),
+                    eta,
+                }),
+            });
+        }
 
         let unit_dir = ray_in.dir().unit_vector();
         let cos_theta = -unit_dir.dot(hit_record.normal);
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
-        let bouncing_vec = match (ir * sin_theta) > 1.0
-            || Dieletric::reflectance(ir, cos_theta) > random_generator.gen_range(0.0..1.0)
-        {
-            true => unit_dir.reflect(hit_record.normal),
-            false => unit_dir.refract(hit_record.normal, ir, cos_theta),
-        };
-
-        (
-            Ray {
+        // past the critical angle the reflectance is 1, every ray is reflected. The radiance
+        // crossing the surface is squeezed into a cone eta times narrower or wider, like the
+        // rough refraction's
+        let (bouncing_vec, attenuation) =
+            match fresnel_dielectric(cos_theta, eta) > random_generator.gen_range(0.0..1.0) {
+                true => (unit_dir.reflect(hit_record.normal), Rgb::white()),
+                false => (
+                    unit_dir.refract(hit_record.normal, 1.0 / eta, cos_theta),
+                    Rgb::white() * (1.0 / (eta * eta)),
+                ),
+            };
+
+        Some(Scatter::Specular {
+            ray: Ray {
                 origin: hit_record.intersection,
                 direction: bouncing_vec,
+                time: ray_in.time(),
             },
-            Rgb::white(),
-        )
+            attenuation,
+        })
+    }
+
+    fn scattering(&self, ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Rgb {
+        let (frame, wo) = local_wo(ray_in, hit_record);
+        let wi = frame.to_local(scattered.dir().unit_vector());
+        if wo.k <= 0.0 {
+            return Rgb::new(0.0, 0.0, 0.0);
+        }
+        let eta = self.eta(hit_record);
+        let ggx = self.ggx();
+
+        if wi.k > 0.0 {
+            let m = (wo + wi).unit_vector();
+            let reflectance = fresnel_dielectric(wo.dot(m), eta);
+            return Rgb::white() * (reflectance * ggx.d(m) * ggx.g(wo, wi) / (4.0 * wo.k));
+        }
+
+        let Some(m) = refraction_half_vector(wo, wi, eta) else {
+            return Rgb::new(0.0, 0.0, 0.0);
+        };
+        // Walter et al.'s BTDF for radiance, its 1 / eta^2 cancels with the eta^2 of the
+        // change from microfacet normals to refracted directions
+        let cos_o = wo.dot(m);
+        let cos_i = wi.dot(m);
+        let transmittance = 1.0 - fresnel_dielectric(cos_o, eta);
+        Rgb::white()
+            * (transmittance * ggx.d(m) * ggx.g(wo, wi) * (cos_o * cos_i).abs()
+                / ((cos_o + eta * cos_i).powi(2) * wo.k))
+    }
+}
+
+/// Emits `emit` from both sides of the shape and absorbs every ray hitting it. The values can
+/// go well above 1, that's the radiance of the light.
+pub struct DiffuseLight {
+    pub emit: Rgb,
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _: &Ray, _: &HitRecord, _: &mut RandomGenerator) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _: &HitRecord) -> Rgb {
+        self.emit
+    }
+}
+
+/// Scatters evenly in every direction whatever the way the ray came from, the phase function
+/// of fog and smoke in a `ConstantMedium`.
+pub struct Isotropic {
+    pub albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: impl Texture + 'static) -> Isotropic {
+        Isotropic {
+            albedo: Arc::new(albedo),
+        }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, _: &Ray, _: &HitRecord, _: &mut RandomGenerator) -> Option<Scatter> {
+        Some(Scatter::Diffuse {
+            pdf: Box::new(SpherePdf),
+        })
+    }
+
+    fn scattering(&self, _: &Ray, hit_record: &HitRecord, _: &Ray) -> Rgb {
+        self.albedo
+            .value(hit_record.u, hit_record.v, hit_record.intersection)
+            * (1.0 / (4.0 * PI))
     }
 }
 
@@ -268,9 +602,244 @@ mod tests {
     }
 
     #[test]
-    fn test_to_gamma() {
-        let c1 = Rgb::new(4.0, 4.0, 4.0).to_gamma();
+    fn test_to_srgb() {
+        let c1 = Rgb::new(0.0, 1.0, 0.5).to_srgb();
+        let c2 = Rgb::new(-1.0, 4.0, 0.001).to_srgb();
+
+        assert!(c1.r == 0.0 && (c1.g - 1.0).abs() < 1e-12);
+        assert!((c1.b - 0.735357).abs() < 1e-6);
+        assert!(c2.r == 0.0 && (c2.g - 1.0).abs() < 1e-12);
+        assert!((c2.b - 0.01292).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_diffuse_light() {
+        let light = Arc::new(DiffuseLight {
+            emit: Rgb::new(4.0, 2.0, 1.0),
+        });
+        let hit_record = HitRecord {
+            intersection: Vec3::new(0.0, 0.0, 0.0),
+            t: 1.0,
+            normal: Vec3::new(0.0, 0.0, 1.0),
+            out_facing: true,
+            u: 0.0,
+            v: 0.0,
+            material: light.clone(),
+        };
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let emitted = light.emitted(&hit_record);
+
+        assert!(light
+            .scatter(&ray, &hit_record, &mut RandomGenerator::new(0))
+            .is_none());
+        assert!(emitted.r == 4.0 && emitted.g == 2.0 && emitted.b == 1.0);
+    }
+
+    #[test]
+    fn test_lambertian_pdf() {
+        let lambertian = Arc::new(Lambertian::new(Rgb::white()));
+        let hit_record = HitRecord {
+            intersection: Vec3::new(0.0, 0.0, 0.0),
+            t: 1.0,
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            out_facing: true,
+            u: 0.0,
+            v: 0.0,
+            material: lambertian.clone(),
+        };
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 1.0), Vec3::new(0.0, -1.0, -1.0));
+        let random_generator = &mut RandomGenerator::new(0);
+
+        let Some(Scatter::Diffuse { pdf }) =
+            lambertian.scatter(&ray, &hit_record, random_generator)
+        else {
+            panic!("lambertian should scatter diffusely");
+        };
+        // a white surface sampled along the cosine reflects everything it receives
+        for _ in 0..100 {
+            let scattered = Ray::new(hit_record.intersection, pdf.generate(random_generator));
+            let scattering = lambertian.scattering(&ray, &hit_record, &scattered);
 
-        assert!(c1.r == 2.0 && c1.g == 2.0 && c1.b == 2.0);
+            assert!(scattered.dir().j >= 0.0);
+            assert!((scattering.g - pdf.value(scattered.dir())).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_fuzz_pdf() {
+        let random_generator = &mut RandomGenerator::new(0);
+        for fuzz in [0.3, 0.5, 1.0] {
+            let pdf = FuzzPdf {
+                reflected: Vec3::new(0.6, 0.8, 0.0),
+                fuzz,
+            };
+            let samples = 200000;
+            let integral = (0..samples)
+                .map(|_| pdf.value(Vec3::random_unit_vector(random_generator)))
+                .sum::<f64>()
+                * 4.0
+                * PI
+                / samples as f64;
+
+            assert!(
+                (integral - 1.0).abs() < 0.03,
+                "fuzz {} integral {}",
+                fuzz,
+                integral
+            );
+        }
+    }
+
+    #[test]
+    fn test_conductor() {
+        let silver = Arc::new(Conductor::preset("silver", 0.3).unwrap());
+        let hit_record = HitRecord {
+            intersection: Vec3::new(0.0, 0.0, 0.0),
+            t: 1.0,
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            out_facing: true,
+            u: 0.0,
+            v: 0.0,
+            material: silver.clone(),
+        };
+        let random_generator = &mut RandomGenerator::new(0);
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 1.0), Vec3::new(0.0, -1.0, -1.0));
+
+        let Some(Scatter::Diffuse { pdf }) = silver.scatter(&ray, &hit_record, random_generator)
+        else {
+            panic!("a rough conductor should scatter diffusely");
+        };
+        // the masking loses some light and the metal absorbs a bit, but it never creates any
+        let samples = 100000;
+        let mut albedo = Rgb::new(0.0, 0.0, 0.0);
+        for _ in 0..samples {
+            let scattered = Ray::new(hit_record.intersection, pdf.generate(random_generator));
+            let pdf_value = pdf.value(scattered.dir());
+            if pdf_value > 0.0 {
+                albedo =
+                    albedo + silver.scattering(&ray, &hit_record, &scattered) * (1.0 / pdf_value);
+            }
+        }
+        let albedo = albedo / samples;
+        assert!(albedo.max_component() < 1.0);
+        assert!(albedo.r > 0.85 && albedo.b > 0.85);
+
+        let Some(Scatter::Specular { attenuation, .. }) = Conductor::preset("gold", 0.0)
+            .unwrap()
+            .scatter(&ray, &hit_record, random_generator)
+        else {
+            panic!("a smooth conductor should be a mirror");
+        };
+        // gold reflects the red light more than the blue
+        assert!(attenuation.r > attenuation.g && attenuation.g > attenuation.b);
+        assert!(METAL_NAMES
+            .iter()
+            .all(|name| Conductor::preset(name, 0.1).is_some()));
+    }
+
+    #[test]
+    fn test_rough_dielectric() {
+        let glass = Arc::new(Dieletric::new(1.5, 0.3));
+        let random_generator = &mut RandomGenerator::new(0);
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 1.0), Vec3::new(0.0, -1.0, -1.0));
+        for out_facing in [true, false] {
+            let hit_record = HitRecord {
+                intersection: Vec3::new(0.0, 0.0, 0.0),
+                t: 1.0,
+                normal: Vec3::new(0.0, 1.0, 0.0),
+                out_facing,
+                u: 0.0,
+                v: 0.0,
+                material: glass.clone(),
+            };
+            let eta = glass.eta(&hit_record);
+            let Some(Scatter::Diffuse { pdf }) = glass.scatter(&ray, &hit_record, random_generator)
+            else {
+                panic!("a rough dielectric should scatter diffusely");
+            };
+
+            // the radiance crossing the surface scales by 1 / eta^2 but the energy is kept,
+            // apart from what the masking loses
+            let samples = 100000;
+            let mut energy = 0.0;
+            for _ in 0..samples {
+                let scattered = Ray::new(hit_record.intersection, pdf.generate(random_generator));
+                let weight =
+                    glass.scattering(&ray, &hit_record, &scattered).g / pdf.value(scattered.dir());
+                energy += match scattered.dir().j > 0.0 {
+                    true => weight,
+                    false => weight * eta * eta,
+                };
+            }
+            let energy = energy / samples as f64;
+            assert!(energy < 1.0 && energy > 0.9, "eta {}: {}", eta, energy);
+        }
+    }
+
+    // the smooth and the barely rough refractions carry the same radiance across the surface
+    #[test]
+    fn test_smooth_refraction() {
+        let random_generator = &mut RandomGenerator::new(1);
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        for out_facing in [true, false] {
+            let mut transmitted = [0.0; 2];
+            for (i, roughness) in [0.0, 0.03].into_iter().enumerate() {
+                let glass = Arc::new(Dieletric::new(1.5, roughness));
+                let hit_record = HitRecord {
+                    intersection: Vec3::new(0.0, 0.0, 0.0),
+                    t: 1.0,
+                    normal: Vec3::new(0.0, 1.0, 0.0),
+                    out_facing,
+                    u: 0.0,
+                    v: 0.0,
+                    material: glass.clone(),
+                };
+                let samples = 20000;
+                for _ in 0..samples {
+                    transmitted[i] += match glass.scatter(&ray, &hit_record, random_generator) {
+                        Some(Scatter::Specular { ray, attenuation }) if ray.dir().j < 0.0 => {
+                            attenuation.g
+                        }
+                        Some(Scatter::Diffuse { pdf }) => {
+                            let scattered =
+                                Ray::new(Vec3::new(0.0, 0.0, 0.0), pdf.generate(random_generator));
+                            match scattered.dir().j < 0.0 {
+                                true => {
+                                    glass.scattering(&ray, &hit_record, &scattered).g
+                                        / pdf.value(scattered.dir())
+                                }
+                                false => 0.0,
+                            }
+                        }
+                        _ => 0.0,
+                    } / samples as f64;
+                }
+            }
+
+            // 96% gets through head on, and the radiance scales by 1 / eta^2
+            let eta: f64 = if out_facing { 1.5 } else { 1.0 / 1.5 };
+            let expected = 0.96 / (eta * eta);
+            assert!(
+                (transmitted[0] - expected).abs() < 0.02 * expected,
+                "{:?}",
+                transmitted
+            );
+            assert!(
+                (transmitted[1] - transmitted[0]).abs() < 0.02 * expected,
+                "{:?}",
+                transmitted
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_component() {
+        assert!(Rgb::new(0.2, 0.7, 0.5).max_component() == 0.7);
+    }
+
+    #[test]
+    fn test_luminance() {
+        assert!((Rgb::white().luminance() - 1.0).abs() < 1e-12);
+        assert!(Rgb::new(0.0, 1.0, 0.0).luminance() == 0.7152);
     }
 }
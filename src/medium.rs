@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    random::RandomGenerator,
+    ray::Ray,
+    vec3::Vec3,
+};
+
+// gap between the entry hit and the search for the exit one, so it isn't found again
+const BOUNDARY_EPSILON: f64 = 0.0001;
+
+/// Smoke, fog or mist with the same density everywhere inside `boundary`. A ray going through
+/// travels an exponentially distributed distance before it bumps into a particle, where
+/// `phase` (usually an `Isotropic` material) scatters it, or leaves on the other side. The
+/// boundary has to be closed and convex: the ray is inside between its first two hits.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    density: f64,
+    phase: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    // `density` is the chance of a scattering per unit of distance
+    pub fn new(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        phase: Arc<dyn Material>,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            density,
+            phase,
+        }
+    }
+}
+
+// `hit` has no random generator, so the distance is drawn from a hash of the ray instead:
+// the same ray always stops at the same spot, which keeps renders reproducible, and any other
+// ray gets an unrelated value
+fn ray_sample(ray: &Ray) -> f64 {
+    let (o, d) = (ray.origin(), ray.dir());
+    let seed = [o.i, o.j, o.k, d.i, d.j, d.k, ray.time()]
+        .iter()
+        .fold(0, |seed: u64, value| {
+            (seed ^ value.to_bits())
+                .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+                .rotate_left(29)
+        });
+    RandomGenerator::new(seed).gen_range(0.0..1.0)
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        // the whole chord through the boundary, the ray may start inside
+        let enter = self.boundary.hit(ray, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self
+            .boundary
+            .hit(ray, enter.t + BOUNDARY_EPSILON, f64::INFINITY)?;
+        let t_enter = enter.t.max(ray_tmin);
+        let t_exit = exit.t.min(ray_tmax);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let ray_length = ray.dir().length();
+        let distance_inside = (t_exit - t_enter) * ray_length;
+        let distance = -(1.0 - ray_sample(ray)).ln() / self.density;
+        if distance > distance_inside {
+            return None;
+        }
+
+        let t = t_enter + distance / ray_length;
+        Some(HitRecord {
+            intersection: ray.at(t),
+            t,
+            // a particle has no surface, neither is used by the phase function
+            normal: Vec3::new(1.0, 0.0, 0.0),
+            out_facing: true,
+            u: 0.0,
+            v: 0.0,
+            material: self.phase.clone(),
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::{Isotropic, Rgb},
+        shape::{Cuboid, Sphere},
+        vec3::Point,
+    };
+
+    fn medium(density: f64) -> ConstantMedium {
+        let phase: Arc<dyn Material> = Arc::new(Isotropic::new(Rgb::white()));
+        let boundary = Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0, phase.clone());
+        ConstantMedium::new(Arc::new(boundary), density, phase)
+    }
+
+    #[test]
+    fn test_density() {
+        let ray = Ray::new(Point::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -2.0));
+        let thick = medium(1e9).hit(&ray, 0.0, f64::INFINITY).unwrap();
+
+        assert!((thick.t - 2.0).abs() < 1e-6);
+        assert!(medium(1e-9).hit(&ray, 0.0, f64::INFINITY).is_none());
+        // the same ray always stops at the same spot
+        let t1 = medium(1.0).hit(&ray, 0.0, f64::INFINITY).map(|hit| hit.t);
+        let t2 = medium(1.0).hit(&ray, 0.0, f64::INFINITY).map(|hit| hit.t);
+        assert!(t1 == t2);
+    }
+
+    #[test]
+    fn test_ray_inside() {
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let hit_record = medium(1e9).hit(&ray, 0.001, f64::INFINITY).unwrap();
+
+        assert!((hit_record.t - 0.001).abs() < 1e-6);
+        assert!(medium(1e9).hit(&ray, 0.001, 0.0005).is_none());
+    }
+
+    #[test]
+    fn test_transmittance() {
+        // through a slab 2 thick with density 0.5, exp(-1) of the rays get across
+        let phase: Arc<dyn Material> = Arc::new(Isotropic::new(Rgb::white()));
+        let slab = Cuboid::new(
+            Point::new(-1e3, -1e3, -1.0),
+            Point::new(1e3, 1e3, 1.0),
+            phase.clone(),
+        );
+        let fog = ConstantMedium::new(Arc::new(slab), 0.5, phase);
+        let rays = 20000;
+        let through = (0..rays)
+            .filter(|&ix| {
+                let origin = Point::new(ix as f64 * 0.01, 0.0, 5.0);
+                let ray = Ray::new(origin, Vec3::new(0.0, 0.0, -1.0));
+                fog.hit(&ray, 0.0, f64::INFINITY).is_none()
+            })
+            .count();
+
+        assert!((through as f64 / rays as f64 - (-1.0_f64).exp()).abs() < 0.01);
+    }
+}
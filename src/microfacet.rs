@@ -0,0 +1,381 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::{material::Rgb, pdf::Pdf, random::RandomGenerator, vec3::Vec3};
+
+/// Coordinates around a surface normal, which is the local +z axis.
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    pub normal: Vec3,
+}
+
+impl Frame {
+    // `normal` should be a unit vector
+    pub fn new(normal: Vec3) -> Frame {
+        let (tangent, bitangent) = normal.tangent_frame();
+        Frame {
+            tangent,
+            bitangent,
+            normal,
+        }
+    }
+
+    pub fn to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            v.dot(self.tangent),
+            v.dot(self.bitangent),
+            v.dot(self.normal),
+        )
+    }
+
+    pub fn to_world(&self, v: Vec3) -> Vec3 {
+        v.i * self.tangent + v.j * self.bitangent + v.k * self.normal
+    }
+}
+
+/// The Trowbridge-Reitz (GGX) distribution of the normals of the microfacets of a rough
+/// surface, in the local frame of the surface. `alpha` is the width of the distribution,
+/// near 0 is almost a mirror.
+#[derive(Clone, Copy, Debug)]
+pub struct Ggx {
+    pub alpha: f64,
+}
+
+impl Ggx {
+    // the usual remapping, it makes the change of appearance with `roughness` look linear
+    pub fn from_roughness(roughness: f64) -> Ggx {
+        Ggx {
+            alpha: roughness * roughness,
+        }
+    }
+
+    /// Density of microfacets with the normal `m`, per unit of projected area of the surface.
+    pub fn d(&self, m: Vec3) -> f64 {
+        if m.k <= 0.0 {
+            return 0.0;
+        }
+        let alpha2 = self.alpha * self.alpha;
+        let denominator = m.k * m.k * (alpha2 - 1.0) + 1.0;
+        alpha2 / (PI * denominator * denominator)
+    }
+
+    // Smith's auxiliary function, the masking is 1 / (1 + lambda)
+    fn lambda(&self, w: Vec3) -> f64 {
+        let cos2 = w.k * w.k;
+        if cos2 == 0.0 {
+            return f64::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        0.5 * ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0)
+    }
+
+    /// Fraction of the microfacets facing `w` that are visible from `w`.
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Fraction of the microfacets visible from both `wo` and `wi`, the height correlated
+    /// Smith masking-shadowing.
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Picks a microfacet normal among those visible from `wo`, following their projected
+    /// area, with Heitz's method. `wo` must be above the surface.
+    pub fn sample_visible_normal(&self, wo: Vec3, u1: f64, u2: f64) -> Vec3 {
+        // stretch the view so the distribution becomes a hemisphere, and sample its projection
+        let v = Vec3::new(self.alpha * wo.i, self.alpha * wo.j, wo.k).unit_vector();
+        let length2 = v.i * v.i + v.j * v.j;
+        let t1 = match length2 > 0.0 {
+            true => Vec3::new(-v.j, v.i, 0.0) / length2.sqrt(),
+            false => Vec3::new(1.0, 0.0, 0.0),
+        };
+        let t2 = v.cross(t1);
+
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + v.k);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let n = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * v;
+
+        Vec3::new(self.alpha * n.i, self.alpha * n.j, n.k.max(1e-6)).unit_vector()
+    }
+
+    /// Density of `sample_visible_normal` picking `m`.
+    pub fn visible_normal_pdf(&self, wo: Vec3, m: Vec3) -> f64 {
+        if wo.k <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(m).max(0.0) * self.d(m) / wo.k
+    }
+}
+
+/// Mirror reflections of `wo` on the visible microfacets of a `Ggx` surface, in world space.
+pub struct GgxReflectionPdf {
+    pub frame: Frame,
+    pub wo: Vec3, // local, toward where the light goes
+    pub ggx: Ggx,
+}
+
+impl Pdf for GgxReflectionPdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        let wi = self.frame.to_local(direction.unit_vector());
+        if wi.k <= 0.0 {
+            return 0.0;
+        }
+        // the change from normals to reflected directions divides by 4 (wo . m)
+        let m = (self.wo + wi).unit_vector();
+        let cos = self.wo.dot(m);
+        if cos <= 0.0 {
+            return 0.0;
+        }
+        self.ggx.visible_normal_pdf(self.wo, m) / (4.0 * cos)
+    }
+
+    fn generate(&self, random_generator: &mut RandomGenerator) -> Vec3 {
+        let m = self.ggx.sample_visible_normal(
+            self.wo,
+            random_generator.gen_range(0.0..1.0),
+            random_generator.gen_range(0.0..1.0),
+        );
+        let wi = 2.0 * self.wo.dot(m) * m - self.wo;
+        self.frame.to_world(wi)
+    }
+}
+
+/// Reflections and refractions of `wo` on the visible microfacets of a `Ggx` interface between
+/// two transparent media, picked by their Fresnel reflectance. `eta` is the index of refraction
+/// below the surface over the one above it.
+pub struct GgxDielectricPdf {
+    pub frame: Frame,
+    pub wo: Vec3, // local, toward where the light goes
+    pub ggx: Ggx,
+    pub eta: f64,
+}
+
+impl GgxDielectricPdf {
+    fn reflection_value(&self, wi: Vec3) -> f64 {
+        let m = (self.wo + wi).unit_vector();
+        let cos = self.wo.dot(m);
+        if cos <= 0.0 {
+            return 0.0;
+        }
+        self.ggx.visible_normal_pdf(self.wo, m) * fresnel_dielectric(cos, self.eta) / (4.0 * cos)
+    }
+
+    fn transmission_value(&self, wi: Vec3) -> f64 {
+        let Some(m) = refraction_half_vector(self.wo, wi, self.eta) else {
+            return 0.0;
+        };
+        let cos_o = self.wo.dot(m);
+        let cos_i = wi.dot(m);
+        let denominator = (cos_o + self.eta * cos_i).powi(2);
+        self.ggx.visible_normal_pdf(self.wo, m)
+            * (1.0 - fresnel_dielectric(cos_o, self.eta))
+            * self.eta
+            * self.eta
+            * cos_i.abs()
+            / denominator
+    }
+}
+
+impl Pdf for GgxDielectricPdf {
+    // both terms on both sides: a reflection can go below the surface, and a refraction above
+    // it, a density missing those would weight the other samples wrong
+    fn value(&self, direction: Vec3) -> f64 {
+        let wi = self.frame.to_local(direction.unit_vector());
+        self.reflection_value(wi) + self.transmission_value(wi)
+    }
+
+    fn generate(&self, random_generator: &mut RandomGenerator) -> Vec3 {
+        let m = self.ggx.sample_visible_normal(
+            self.wo,
+            random_generator.gen_range(0.0..1.0),
+            random_generator.gen_range(0.0..1.0),
+        );
+        let cos = self.wo.dot(m);
+        // a total internal reflection has a reflectance of 1
+        let wi = match fresnel_dielectric(cos, self.eta) > random_generator.gen_range(0.0..1.0) {
+            true => 2.0 * cos * m - self.wo,
+            false => (-self.wo).refract(m, 1.0 / self.eta, cos),
+        };
+        self.frame.to_world(wi)
+    }
+}
+
+/// The microfacet normal that refracts `wo` into `wi`, facing up, or `None` when no microfacet
+/// does. `eta` is the index of refraction on the side of `wi` over the one of `wo`.
+pub fn refraction_half_vector(wo: Vec3, wi: Vec3, eta: f64) -> Option<Vec3> {
+    let m = wo + eta * wi;
+    if m.length_pow2() == 0.0 {
+        return None;
+    }
+    let m = m.unit_vector();
+    let m = if m.k < 0.0 { -m } else { m };
+    match wo.dot(m) > 0.0 && wi.dot(m) < 0.0 {
+        true => Some(m),
+        false => None,
+    }
+}
+
+/// Exact unpolarized reflectance of the interface between two dielectrics, for light arriving
+/// at `cos_i` from the normal. `eta` is the index of refraction on the other side over the one
+/// on the side of the light, the reflectance is 1 past the critical angle.
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular)
+}
+
+/// Exact unpolarized reflectance of a conductor with the complex index of refraction
+/// `eta + i k`, for light arriving at `cos_i` from the normal.
+pub fn fresnel_conductor(cos_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cos_i.clamp(0.0, 1.0) * a;
+    let rs = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    0.5 * (rs + rp)
+}
+
+/// `fresnel_conductor` for each channel.
+pub fn fresnel_conductor_rgb(cos_i: f64, eta: Rgb, k: Rgb) -> Rgb {
+    Rgb::new(
+        fresnel_conductor(cos_i, eta.r, k.r),
+        fresnel_conductor(cos_i, eta.g, k.g),
+        fresnel_conductor(cos_i, eta.b, k.b),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uniform_integral(f: impl Fn(Vec3) -> f64) -> f64 {
+        let random_generator = &mut RandomGenerator::new(5);
+        let samples = 400000;
+        (0..samples)
+            .map(|_| f(Vec3::random_unit_vector(random_generator)))
+            .sum::<f64>()
+            * 4.0
+            * PI
+            / samples as f64
+    }
+
+    #[test]
+    fn test_frame() {
+        let frame = Frame::new(Vec3::new(1.0, 2.0, 2.0) / 3.0);
+        let v = Vec3::new(0.3, -0.2, 0.9);
+        let back = frame.to_world(frame.to_local(v));
+
+        assert!((frame.to_local(frame.normal).k - 1.0).abs() < 1e-12);
+        assert!((back - v).length() < 1e-12);
+    }
+
+    // the projected areas of the microfacets add up to the area of the surface, and the
+    // visible ones to its area seen from any direction
+    #[test]
+    fn test_ggx_normalization() {
+        let wo = Vec3::new(0.6, 0.0, 0.8);
+        for alpha in [0.2, 0.5, 1.0] {
+            let ggx = Ggx { alpha };
+            let projected = uniform_integral(|m| ggx.d(m) * m.k.max(0.0));
+            let visible = uniform_integral(|m| ggx.visible_normal_pdf(wo, m));
+
+            assert!(
+                (projected - 1.0).abs() < 0.03,
+                "alpha {}: {}",
+                alpha,
+                projected
+            );
+            assert!((visible - 1.0).abs() < 0.03, "alpha {}: {}", alpha, visible);
+        }
+    }
+
+    #[test]
+    fn test_reflection_pdf() {
+        let random_generator = &mut RandomGenerator::new(2);
+        let pdf = GgxReflectionPdf {
+            frame: Frame::new(Vec3::new(0.0, 1.0, 0.0)),
+            wo: Vec3::new(0.0, 0.6, 0.8),
+            ggx: Ggx { alpha: 0.5 },
+        };
+        // some reflections go below the surface, the density is the fraction of the others
+        let samples = 100000;
+        let above = (0..samples)
+            .map(|_| pdf.generate(random_generator))
+            .filter(|direction| direction.j > 0.0 && pdf.value(*direction) > 0.0)
+            .count();
+        let integral = uniform_integral(|w| pdf.value(w));
+
+        assert!(
+            (integral - above as f64 / samples as f64).abs() < 0.03,
+            "integral {} above {}",
+            integral,
+            above
+        );
+    }
+
+    // every sample lands somewhere, the density covers both sides of the surface
+    #[test]
+    fn test_dielectric_pdf() {
+        let random_generator = &mut RandomGenerator::new(3);
+        for eta in [1.5, 1.0 / 1.5] {
+            let pdf = GgxDielectricPdf {
+                frame: Frame::new(Vec3::new(0.0, 1.0, 0.0)),
+                wo: Vec3::new(0.0, 0.6, 0.8),
+                ggx: Ggx { alpha: 0.3 },
+                eta,
+            };
+            let integral = uniform_integral(|w| pdf.value(w));
+
+            assert!((integral - 1.0).abs() < 0.03, "eta {}: {}", eta, integral);
+            for _ in 0..100 {
+                assert!(pdf.value(pdf.generate(random_generator)) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fresnel_dielectric() {
+        // ((eta - 1) / (eta + 1))^2 at normal incidence from either side
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-12);
+        assert!((fresnel_dielectric(1.0, 1.0 / 1.5) - 0.04).abs() < 1e-12);
+        assert!((fresnel_dielectric(0.0, 1.5) - 1.0).abs() < 1e-12);
+        // inside glass, total internal reflection past the critical angle, cos = 0.745
+        let near_critical = fresnel_dielectric(0.76, 1.0 / 1.5);
+        assert!(near_critical > 0.04 && near_critical < 1.0);
+        assert!(fresnel_dielectric(0.74, 1.0 / 1.5) == 1.0);
+        assert!(fresnel_dielectric(1.0, 1.0) == 0.0);
+    }
+
+    #[test]
+    fn test_fresnel_conductor() {
+        // at normal incidence ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2), and 1 at grazing angles
+        let normal = fresnel_conductor(1.0, 0.2, 3.9);
+        let expected = (0.8 * 0.8 + 3.9 * 3.9) / (1.2 * 1.2 + 3.9 * 3.9);
+        assert!((normal - expected).abs() < 1e-12);
+        assert!((fresnel_conductor(0.0, 0.2, 3.9) - 1.0).abs() < 1e-12);
+
+        // without absorption it's glass seen from the outside, 4% head on
+        assert!((fresnel_conductor(1.0, 1.5, 0.0) - 0.04).abs() < 1e-12);
+    }
+}
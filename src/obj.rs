@@ -0,0 +1,587 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    hittable::HittableList,
+    material::{Dieletric, DiffuseLight, Lambertian, Material, Metal, Rgb},
+    shape::{MeshFace, TriangleMesh},
+    vec3::{Point, Vec3},
+};
+
+/// Everything that can go wrong while importing an OBJ file or its MTL libraries.
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    BadIndex {
+        path: PathBuf,
+        line: usize,
+        index: i64,
+    },
+    UnknownMaterial {
+        path: PathBuf,
+        line: usize,
+        name: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            ObjError::BadIndex { path, line, index } => {
+                write!(
+                    f,
+                    "{}:{}: index {} out of range",
+                    path.display(),
+                    line,
+                    index
+                )
+            }
+            ObjError::UnknownMaterial { path, line, name } => {
+                write!(
+                    f,
+                    "{}:{}: unknown material `{}`",
+                    path.display(),
+                    line,
+                    name
+                )
+            }
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// One `newmtl` entry of an MTL library, only the parameters the renderer can use.
+#[derive(Clone, Debug)]
+pub struct MtlEntry {
+    pub diffuse: Rgb,   // Kd
+    pub specular: Rgb,  // Ks
+    pub emission: Rgb,  // Ke
+    pub shininess: f64, // Ns
+    pub ior: f64,       // Ni
+    pub dissolve: f64,  // d, or 1 - Tr
+    pub illum: u32,
+}
+
+impl Default for MtlEntry {
+    fn default() -> MtlEntry {
+        MtlEntry {
+            diffuse: Rgb::new(0.8, 0.8, 0.8),
+            specular: Rgb::new(0.0, 0.0, 0.0),
+            emission: Rgb::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            ior: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+}
+
+impl MtlEntry {
+    /// Emissive entries become `DiffuseLight`, transparent ones `Dieletric`, mirror-like ones
+    /// `Metal` and the rest `Lambertian`.
+    pub fn to_material(&self) -> Arc<dyn Material> {
+        if self.emission.r.max(self.emission.g).max(self.emission.b) > 0.0 {
+            return Arc::new(DiffuseLight {
+                emit: self.emission,
+            });
+        }
+
+        let transparent = matches!(self.illum, 4 | 6 | 7 | 9) || self.dissolve < 1.0;
+        let specular = self.specular.r.max(self.specular.g).max(self.specular.b);
+        let diffuse = self.diffuse.r.max(self.diffuse.g).max(self.diffuse.b);
+
+        if transparent {
+            Arc::new(Dieletric::new(self.ior, 0.0))
+        } else if self.illum == 3 || (specular > 0.0 && diffuse == 0.0) {
+            // the usual Phong exponent to roughness conversion
+            Arc::new(Metal::new(
+                self.specular,
+                (2.0 / (self.shininess + 2.0)).sqrt(),
+            ))
+        } else {
+            Arc::new(Lambertian::new(self.diffuse))
+        }
+    }
+}
+
+fn parse_error(path: &Path, line: usize, message: impl Into<String>) -> ObjError {
+    ObjError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message: message.into(),
+    }
+}
+
+fn parse_floats<const N: usize>(
+    path: &Path,
+    line: usize,
+    args: &[&str],
+) -> Result<[f64; N], ObjError> {
+    if args.len() < N {
+        return Err(parse_error(
+            path,
+            line,
+            format!("expected {} numbers, found {}", N, args.len()),
+        ));
+    }
+
+    let mut values = [0.0; N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg
+            .parse()
+            .map_err(|_| parse_error(path, line, format!("invalid number `{}`", arg)))?;
+    }
+    Ok(values)
+}
+
+/// Parses the content of an MTL library, `path` is only used in the error messages.
+pub fn parse_mtl(source: &str, path: &Path) -> Result<HashMap<String, MtlEntry>, ObjError> {
+    let mut entries = HashMap::new();
+    let mut current: Option<(String, MtlEntry)> = None;
+
+    for (ix, raw_line) in source.lines().enumerate() {
+        let line = ix + 1;
+        let mut words = raw_line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let args: Vec<&str> = words.collect();
+
+        if keyword == "newmtl" {
+            if let Some((name, entry)) = current.take() {
+                entries.insert(name, entry);
+            }
+            let name = args.join(" ");
+            if name.is_empty() {
+                return Err(parse_error(path, line, "newmtl without a name"));
+            }
+            current = Some((name, MtlEntry::default()));
+            continue;
+        }
+
+        if keyword.starts_with('#') {
+            continue;
+        }
+
+        let Some((_, entry)) = current.as_mut() else {
+            return Err(parse_error(
+                path,
+                line,
+                format!("`{}` before any newmtl", keyword),
+            ));
+        };
+        match keyword {
+            "Kd" => {
+                let [r, g, b] = parse_floats(path, line, &args)?;
+                entry.diffuse = Rgb::new(r, g, b);
+            }
+            "Ks" => {
+                let [r, g, b] = parse_floats(path, line, &args)?;
+                entry.specular = Rgb::new(r, g, b);
+            }
+            "Ke" => {
+                let [r, g, b] = parse_floats(path, line, &args)?;
+                entry.emission = Rgb::new(r, g, b);
+            }
+            "Ns" => entry.shininess = parse_floats::<1>(path, line, &args)?[0],
+            "Ni" => entry.ior = parse_floats::<1>(path, line, &args)?[0],
+            "d" => entry.dissolve = parse_floats::<1>(path, line, &args)?[0],
+            "Tr" => entry.dissolve = 1.0 - parse_floats::<1>(path, line, &args)?[0],
+            "illum" => {
+                entry.illum = args
+                    .first()
+                    .and_then(|arg| arg.parse().ok())
+                    .ok_or_else(|| parse_error(path, line, "invalid illum"))?;
+            }
+            // ambient, texture maps... are not supported, skip them
+            _ => {}
+        }
+    }
+
+    if let Some((name, entry)) = current {
+        entries.insert(name, entry);
+    }
+    Ok(entries)
+}
+
+// a group of faces sharing the same material
+struct FaceGroup {
+    material: Arc<dyn Material>,
+    faces: Vec<MeshFace>,
+}
+
+// turns a 1-based (or negative, relative to the end) OBJ index into a 0-based one
+fn resolve_index(
+    path: &Path,
+    line: usize,
+    raw: &str,
+    buffer_len: usize,
+) -> Result<usize, ObjError> {
+    let index: i64 = raw
+        .parse()
+        .map_err(|_| parse_error(path, line, format!("invalid index `{}`", raw)))?;
+    let resolved = if index < 0 {
+        buffer_len as i64 + index
+    } else {
+        index - 1
+    };
+
+    if resolved < 0 || resolved >= buffer_len as i64 {
+        return Err(ObjError::BadIndex {
+            path: path.to_path_buf(),
+            line,
+            index,
+        });
+    }
+    Ok(resolved as usize)
+}
+
+/// Parses an OBJ file already read into memory. `mtllib` statements are resolved relative to
+/// `dir`, faces before any `usemtl` use `default_material`. Every material group becomes one
+/// `TriangleMesh` in the returned list.
+pub fn parse_obj(
+    source: &str,
+    path: &Path,
+    dir: &Path,
+    default_material: Arc<dyn Material>,
+) -> Result<HittableList, ObjError> {
+    let mut positions: Vec<Point> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut uvs: Vec<(f64, f64)> = vec![];
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    let mut groups = vec![FaceGroup {
+        material: default_material,
+        faces: vec![],
+    }];
+
+    for (ix, raw_line) in source.lines().enumerate() {
+        let line = ix + 1;
+        let mut words = raw_line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let args: Vec<&str> = words.collect();
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats(path, line, &args)?;
+                positions.push(Point::new(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats(path, line, &args)?;
+                normals.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                // v and the depth w are optional, a 1D texture coordinate has v = 0
+                let [u, v] = match args.len() {
+                    1 => [parse_floats::<1>(path, line, &args)?[0], 0.0],
+                    _ => parse_floats(path, line, &args)?,
+                };
+                uvs.push((u, v));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(parse_error(path, line, "a face needs at least 3 vertices"));
+                }
+
+                let mut vertices = vec![];
+                for arg in &args {
+                    // v, v/vt, v//vn or v/vt/vn
+                    let mut parts = arg.split('/');
+                    let position =
+                        resolve_index(path, line, parts.next().unwrap(), positions.len())?;
+                    let uv = match parts.next() {
+                        Some(raw) if !raw.is_empty() => {
+                            Some(resolve_index(path, line, raw, uvs.len())?)
+                        }
+                        _ => None,
+                    };
+                    let normal = match parts.next() {
+                        Some(raw) if !raw.is_empty() => {
+                            Some(resolve_index(path, line, raw, normals.len())?)
+                        }
+                        _ => None,
+                    };
+                    vertices.push((position, uv, normal));
+                }
+
+                // polygons are split into a fan around their first vertex
+                let faces = &mut groups.last_mut().unwrap().faces;
+                for k in 1..vertices.len() - 1 {
+                    let (p0, t0, n0) = vertices[0];
+                    let (p1, t1, n1) = vertices[k];
+                    let (p2, t2, n2) = vertices[k + 1];
+                    faces.push(MeshFace {
+                        positions: [p0, p1, p2],
+                        normals: n0.zip(n1).zip(n2).map(|((a, b), c)| [a, b, c]),
+                        uvs: t0.zip(t1).zip(t2).map(|((a, b), c)| [a, b, c]),
+                    });
+                }
+            }
+            "mtllib" => {
+                for name in &args {
+                    let mtl_path = dir.join(name);
+                    let mtl_source =
+                        fs::read_to_string(&mtl_path).map_err(|source| ObjError::Io {
+                            path: mtl_path.clone(),
+                            source,
+                        })?;
+                    for (name, entry) in parse_mtl(&mtl_source, &mtl_path)? {
+                        materials.insert(name, entry.to_material());
+                    }
+                }
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                let material =
+                    materials
+                        .get(&name)
+                        .cloned()
+                        .ok_or_else(|| ObjError::UnknownMaterial {
+                            path: path.to_path_buf(),
+                            line,
+                            name,
+                        })?;
+                groups.push(FaceGroup {
+                    material,
+                    faces: vec![],
+                });
+            }
+            // comments, objects, groups, smoothing... don't change the geometry
+            _ => {}
+        }
+    }
+
+    let mut world = HittableList::new();
+    for group in groups.into_iter().filter(|group| !group.faces.is_empty()) {
+        world.push(Arc::new(compact_mesh(
+            &positions,
+            &normals,
+            &uvs,
+            group.faces,
+            group.material,
+        )));
+    }
+    Ok(world)
+}
+
+// copies only the vertices used by `faces` into the buffers of a new mesh
+fn compact_mesh(
+    positions: &[Point],
+    normals: &[Vec3],
+    uvs: &[(f64, f64)],
+    mut faces: Vec<MeshFace>,
+    material: Arc<dyn Material>,
+) -> TriangleMesh {
+    fn remap<T: Copy>(
+        ixs: &mut [usize; 3],
+        source: &[T],
+        used: &mut HashMap<usize, usize>,
+        target: &mut Vec<T>,
+    ) {
+        for ix in ixs.iter_mut() {
+            *ix = *used.entry(*ix).or_insert_with(|| {
+                target.push(source[*ix]);
+                target.len() - 1
+            });
+        }
+    }
+
+    let (mut mesh_positions, mut mesh_normals, mut mesh_uvs) = (vec![], vec![], vec![]);
+    let (mut used_positions, mut used_normals, mut used_uvs) =
+        (HashMap::new(), HashMap::new(), HashMap::new());
+
+    for face in faces.iter_mut() {
+        remap(
+            &mut face.positions,
+            positions,
+            &mut used_positions,
+            &mut mesh_positions,
+        );
+        if let Some(ixs) = face.normals.as_mut() {
+            remap(ixs, normals, &mut used_normals, &mut mesh_normals);
+        }
+        if let Some(ixs) = face.uvs.as_mut() {
+            remap(ixs, uvs, &mut used_uvs, &mut mesh_uvs);
+        }
+    }
+
+    TriangleMesh::new(mesh_positions, mesh_normals, mesh_uvs, faces, material)
+}
+
+/// Loads an OBJ file and the MTL libraries it refers to.
+pub fn load_obj(
+    path: impl AsRef<Path>,
+    default_material: Arc<dyn Material>,
+) -> Result<HittableList, ObjError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    parse_obj(&source, path, dir, default_material)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::Hittable, ray::Ray};
+
+    fn gray() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Rgb::new(0.5, 0.5, 0.5)))
+    }
+
+    fn parse(source: &str) -> Result<HittableList, ObjError> {
+        parse_obj(source, Path::new("test.obj"), Path::new("."), gray())
+    }
+
+    const QUAD: &str = "
+# a unit square made of one polygon
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1 -1/4/1
+";
+
+    #[test]
+    fn test_parse_polygon() {
+        let world = parse(QUAD).unwrap();
+        let ray = Ray::new(Point::new(0.25, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = world.hit(&ray, 0.001, f64::INFINITY).unwrap();
+
+        assert!(world.len() == 1);
+        assert!(rec.t == 1.0 && rec.out_facing);
+        assert!((rec.u - 0.25).abs() < 1e-12 && (rec.v - 0.75).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_short_texture_coordinates() {
+        let source = QUAD
+            .replace("vt 1 1\n", "vt 1\n")
+            .replace("vt 0 1\n", "vt 0.5 1 0\n");
+        let world = parse(&source).unwrap();
+        let ray = Ray::new(Point::new(0.999, 0.999, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = world.hit(&ray, 0.001, f64::INFINITY).unwrap();
+
+        assert!((rec.u - 1.0).abs() < 1e-2 && rec.v.abs() < 1e-2);
+        assert!(parse("vt\n").is_err());
+    }
+
+    #[test]
+    fn test_bad_index() {
+        let err = parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n").err().unwrap();
+
+        assert!(matches!(
+            err,
+            ObjError::BadIndex {
+                line: 3,
+                index: 3,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_invalid_number() {
+        let err = parse("v 0 zero 0\n").err().unwrap();
+
+        assert!(matches!(err, ObjError::Parse { line: 1, .. }));
+    }
+
+    #[test]
+    fn test_unknown_material() {
+        let err = parse("usemtl missing\n").err().unwrap();
+
+        assert!(matches!(err, ObjError::UnknownMaterial { line: 1, .. }));
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let err = load_obj("/nonexistent/model.obj", gray()).err().unwrap();
+
+        assert!(matches!(err, ObjError::Io { .. }));
+    }
+
+    #[test]
+    fn test_parse_mtl() {
+        let source = "
+newmtl red
+Kd 1 0 0
+Ke 0 2 0
+newmtl glass
+Ni 1.33
+d 0.2
+illum 4
+";
+        let entries = parse_mtl(source, Path::new("test.mtl")).unwrap();
+
+        assert!(entries.len() == 2);
+        assert!(entries["red"].diffuse.r == 1.0 && entries["red"].diffuse.g == 0.0);
+        assert!(entries["red"].emission.g == 2.0);
+        assert!(entries["glass"].ior == 1.33 && entries["glass"].dissolve == 0.2);
+        assert!(entries["glass"].illum == 4);
+    }
+
+    #[test]
+    fn test_mtl_before_newmtl() {
+        let err = parse_mtl("Kd 1 1 1\n", Path::new("test.mtl"))
+            .err()
+            .unwrap();
+
+        assert!(matches!(err, ObjError::Parse { line: 1, .. }));
+    }
+
+    #[test]
+    fn test_load_with_mtllib() {
+        let dir = std::env::temp_dir().join("rtoneweekend_obj_test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("scene.mtl"),
+            "newmtl a\nKd 1 0 0\nnewmtl b\nKs 1 1 1\nKd 0 0 0\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("scene.obj"),
+            "mtllib scene.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nusemtl a\nf 1 2 3\nusemtl b\nf 1 2 4\n",
+        )
+        .unwrap();
+
+        let world = load_obj(dir.join("scene.obj"), gray()).unwrap();
+
+        assert!(world.len() == 2);
+    }
+}
@@ -0,0 +1,310 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::{
+    background::Background,
+    hittable::Hittable,
+    random::RandomGenerator,
+    ray::Ray,
+    vec3::{Point, Vec3},
+};
+
+/// A way of picking directions, with the density per steradian of each one so the path tracer
+/// can weight what it finds there: `f(direction) / value(direction)` is an unbiased estimate
+/// whatever the distribution, and has a low variance when it follows `f`.
+pub trait Pdf {
+    fn value(&self, direction: Vec3) -> f64;
+
+    fn generate(&self, random_generator: &mut RandomGenerator) -> Vec3;
+}
+
+/// Every direction equally likely, the density is 1 / 4pi.
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _: Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self, random_generator: &mut RandomGenerator) -> Vec3 {
+        Vec3::random_unit_vector(random_generator)
+    }
+}
+
+/// Directions above a surface following the cosine of their angle with the `normal`, like
+/// the light bouncing off a perfectly diffuse surface.
+pub struct CosinePdf {
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+}
+
+impl CosinePdf {
+    // `normal` should be a unit vector
+    pub fn new(normal: Vec3) -> CosinePdf {
+        let (tangent, bitangent) = normal.tangent_frame();
+        CosinePdf {
+            normal,
+            tangent,
+            bitangent,
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: Vec3) -> f64 {
+        let cos_theta = direction.unit_vector().dot(self.normal);
+        cos_theta.max(0.0) / PI
+    }
+
+    fn generate(&self, random_generator: &mut RandomGenerator) -> Vec3 {
+        let v = Vec3::random_cosine_direction(random_generator);
+        v.i * self.tangent + v.j * self.bitangent + v.k * self.normal
+    }
+}
+
+/// Directions from `origin` toward the points of an object, usually the lights of a scene.
+pub struct HittablePdf<'a> {
+    object: &'a dyn Hittable,
+    origin: Point,
+    time: f64,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(object: &'a dyn Hittable, origin: Point, time: f64) -> HittablePdf<'a> {
+        HittablePdf {
+            object,
+            origin,
+            time,
+        }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        self.object
+            .pdf_value(&Ray::with_time(self.origin, direction, self.time))
+    }
+
+    fn generate(&self, random_generator: &mut RandomGenerator) -> Vec3 {
+        self.object.sample(self.origin, self.time, random_generator)
+    }
+}
+
+/// Directions toward the bright parts of a background that supports sampling, like an
+/// environment map with a sun in it.
+pub struct BackgroundPdf<'a> {
+    background: &'a dyn Background,
+}
+
+impl<'a> BackgroundPdf<'a> {
+    pub fn new(background: &'a dyn Background) -> BackgroundPdf<'a> {
+        BackgroundPdf { background }
+    }
+}
+
+impl Pdf for BackgroundPdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        self.background.pdf(direction.unit_vector())
+    }
+
+    // a background without sampling falls back to any direction, with a density of 0 as far
+    // as `value` is concerned the path tracer shouldn't mix it in at all
+    fn generate(&self, random_generator: &mut RandomGenerator) -> Vec3 {
+        match self.background.sample(random_generator) {
+            Some((direction, _)) => direction,
+            None => Vec3::random_unit_vector(random_generator),
+        }
+    }
+}
+
+/// Picks one of several distributions with the same chance, so the directions are good for
+/// any of them: toward the lights and along the material's own preference, say.
+pub struct MixturePdf<'a> {
+    pdfs: Vec<&'a dyn Pdf>,
+}
+
+impl<'a> MixturePdf<'a> {
+    // panics without any distribution to pick from
+    pub fn new(pdfs: Vec<&'a dyn Pdf>) -> MixturePdf<'a> {
+        assert!(!pdfs.is_empty());
+        MixturePdf { pdfs }
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: Vec3) -> f64 {
+        let sum: f64 = self.pdfs.iter().map(|pdf| pdf.value(direction)).sum();
+        sum / self.pdfs.len() as f64
+    }
+
+    fn generate(&self, random_generator: &mut RandomGenerator) -> Vec3 {
+        let ix = random_generator.gen_range(0..self.pdfs.len());
+        self.pdfs[ix].generate(random_generator)
+    }
+}
+
+/// Multiple importance sampling weight of a sample drawn with density `pdf`, when another
+/// strategy could have drawn it with density `other`: the weights of all the strategies add up
+/// to 1 whatever the direction.
+pub fn balance_heuristic(pdf: f64, other: f64) -> f64 {
+    if pdf + other <= 0.0 {
+        return 0.0;
+    }
+    pdf / (pdf + other)
+}
+
+/// Like `balance_heuristic` with squared densities, it favors the strategy that's best at
+/// each direction even more and usually has a bit less noise.
+pub fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    balance_heuristic(pdf * pdf, other * other)
+}
+
+// hits closer than this are the surface the ray starts from
+const SELF_HIT_EPSILON: f64 = 1e-4;
+
+/// Density per steradian, seen from the origin of `ray`, of picking its direction by picking
+/// a point uniformly on `shape`, whose area is `area`. 0 when the ray misses the shape.
+pub fn solid_angle_pdf(shape: &dyn Hittable, ray: &Ray, area: f64) -> f64 {
+    let Some(hit_record) = shape.hit(ray, SELF_HIT_EPSILON, f64::INFINITY) else {
+        return 0.0;
+    };
+    let length = ray.dir().length();
+    let distance_squared = hit_record.t * hit_record.t * length * length;
+    let cosine = (ray.dir().dot(hit_record.normal) / length).abs();
+    if cosine == 0.0 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        hittable::{HittableList, Transformed},
+        material::{Lambertian, Material, Rgb},
+        shape::{Cuboid, Disk, Quad, Sphere, Triangle},
+        vec3::Transform,
+    };
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Rgb::white()))
+    }
+
+    // the density over all directions should add up to 1, and every sample should be a
+    // direction the density knows about
+    fn check_pdf(pdf: &dyn Pdf) {
+        let random_generator = &mut RandomGenerator::new(1);
+        let samples = 200000;
+        let integral = (0..samples)
+            .map(|_| pdf.value(Vec3::random_unit_vector(random_generator)))
+            .sum::<f64>()
+            * 4.0
+            * PI
+            / samples as f64;
+
+        assert!((integral - 1.0).abs() < 0.03, "integral {}", integral);
+        for _ in 0..100 {
+            assert!(pdf.value(pdf.generate(random_generator)) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_direction_pdfs() {
+        check_pdf(&SpherePdf);
+        check_pdf(&CosinePdf::new(Vec3::new(1.0, 2.0, 3.0).unit_vector()));
+    }
+
+    #[test]
+    fn test_shape_pdfs() {
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let shapes: Vec<Arc<dyn Hittable>> = vec![
+            Arc::new(Sphere::new(Point::new(0.0, 0.0, -2.0), 1.0, material())),
+            Arc::new(Sphere::new(Point::new(0.0, 0.5, 0.0), 1.0, material())),
+            Arc::new(Quad::new(
+                Point::new(-1.0, -1.0, -1.0),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(0.0, 2.0, 0.5),
+                material(),
+            )),
+            Arc::new(Disk::new(
+                Point::new(0.0, 1.0, 0.0),
+                Vec3::new(0.0, -1.0, 1.0),
+                1.5,
+                material(),
+            )),
+            Arc::new(Triangle {
+                a: Point::new(-1.0, -1.0, -1.0),
+                b: Point::new(2.0, -1.0, -1.0),
+                c: Point::new(0.0, 2.0, -1.5),
+                material: material(),
+            }),
+            Arc::new(Cuboid::new(
+                Point::new(-1.0, -1.0, -3.0),
+                Point::new(1.0, 1.0, -1.0),
+                material(),
+            )),
+        ];
+
+        for shape in &shapes {
+            check_pdf(&HittablePdf::new(shape.as_ref(), origin, 0.0));
+        }
+        let lights: HittableList = shapes[2..4].to_vec();
+        check_pdf(&HittablePdf::new(&lights, origin, 0.0));
+    }
+
+    #[test]
+    fn test_transformed_pdf() {
+        let disk = Disk::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            material(),
+        );
+        let stretched = Transformed::new(
+            Arc::new(disk),
+            Transform::scaling(Vec3::new(3.0, 1.0, 1.0))
+                .then(&Transform::rotation(Vec3::new(1.0, 0.0, 0.0), 30.0))
+                .then(&Transform::translation(Vec3::new(0.0, 0.0, -1.5))),
+        );
+
+        check_pdf(&HittablePdf::new(
+            &stretched,
+            Point::new(0.5, 0.0, 0.0),
+            0.0,
+        ));
+    }
+
+    #[test]
+    fn test_heuristics() {
+        assert!(balance_heuristic(1.0, 3.0) == 0.25);
+        assert!(balance_heuristic(0.0, 0.0) == 0.0);
+        assert!(power_heuristic(1.0, 3.0) == 0.1);
+        assert!(power_heuristic(2.0, 0.0) == 1.0);
+        assert!((power_heuristic(0.3, 0.7) + power_heuristic(0.7, 0.3) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_mixture_pdf() {
+        let light = Quad::new(
+            Point::new(-1.0, 1.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 2.0),
+            material(),
+        );
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let towards_light = HittablePdf::new(&light, origin, 0.0);
+        let cosine = CosinePdf::new(Vec3::new(0.0, 1.0, 0.0));
+        let mixture = MixturePdf::new(vec![&towards_light, &cosine]);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+
+        check_pdf(&mixture);
+        assert!(
+            (mixture.value(up) - 0.5 * (towards_light.value(up) + cosine.value(up))).abs() < 1e-12
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use rand::{Error, RngCore};
+
+/// Seedable xoshiro256++ generator. Unlike `ThreadRng`, the same seed always gives the same
+/// sequence, and `split` derives independent streams so every pixel (or tile) can own one
+/// without depending on which thread renders it.
+#[derive(Clone, Debug)]
+pub struct RandomGenerator {
+    state: [u64; 4],
+}
+
+// splitmix64 step, used to expand seeds into the full xoshiro state
+fn splitmix64(x: &mut u64) -> u64 {
+    *x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *x;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl RandomGenerator {
+    pub fn new(seed: u64) -> RandomGenerator {
+        let mut x = seed;
+        RandomGenerator {
+            state: [
+                splitmix64(&mut x),
+                splitmix64(&mut x),
+                splitmix64(&mut x),
+                splitmix64(&mut x),
+            ],
+        }
+    }
+
+    // derive the generator of sub stream `stream`, it doesn't advance `self`
+    pub fn split(&self, stream: u64) -> RandomGenerator {
+        let mut x = stream;
+        let seed = self.state[0] ^ self.state[1].rotate_left(23) ^ splitmix64(&mut x);
+        RandomGenerator::new(seed)
+    }
+}
+
+impl RngCore for RandomGenerator {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+        let t = s[1] << 17;
+
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        result
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut g1 = RandomGenerator::new(42);
+        let mut g2 = RandomGenerator::new(42);
+
+        for _ in 0..100 {
+            assert!(g1.next_u64() == g2.next_u64());
+        }
+    }
+
+    #[test]
+    fn test_different_seed_different_sequence() {
+        let mut g1 = RandomGenerator::new(1);
+        let mut g2 = RandomGenerator::new(2);
+
+        assert!(g1.next_u64() != g2.next_u64());
+    }
+
+    #[test]
+    fn test_split_is_deterministic() {
+        let root = RandomGenerator::new(7);
+        let mut s1 = root.split(3);
+        let mut s2 = root.split(3);
+        let mut s3 = root.split(4);
+        let first = s1.next_u64();
+
+        assert!(first == s2.next_u64());
+        assert!(first != s3.next_u64());
+    }
+
+    #[test]
+    fn test_gen_range() {
+        let mut g = RandomGenerator::new(0);
+
+        for _ in 0..1000 {
+            let x = g.gen_range(-0.5..0.5);
+            assert!((-0.5..0.5).contains(&x));
+        }
+    }
+
+    #[test]
+    fn test_fill_bytes_partial_chunk() {
+        let mut g1 = RandomGenerator::new(9);
+        let mut g2 = RandomGenerator::new(9);
+        let mut bytes = [0u8; 5];
+        g1.fill_bytes(&mut bytes);
+
+        assert!(bytes == g2.next_u64().to_le_bytes()[..5]);
+    }
+}
@@ -3,11 +3,24 @@ use crate::vec3::{Point, Vec3};
 pub struct Ray {
     pub origin: Point,
     pub direction: Vec3,
+    pub time: f64, // when the ray was sent, within the camera's shutter interval
 }
 
 impl Ray {
     pub fn new(origin: Point, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    pub fn with_time(origin: Point, direction: Vec3, time: f64) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 
     pub fn origin(&self) -> Point {
@@ -18,6 +31,10 @@ impl Ray {
         self.direction
     }
 
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
     pub fn at(&self, t: f64) -> Point {
         self.origin + t * self.direction
     }
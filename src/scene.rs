@@ -0,0 +1,1841 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use image::ImageError;
+use rand::Rng;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    background::{Background, Gradient},
+    camera::CameraConfig,
+    environment::EnvironmentMap,
+    hittable::{Hittable, HittableList, Transformed},
+    integrator::{
+        self, AmbientOcclusion, Depth, Integrator, LightSampling, Normals, PathTracer, Whitted,
+    },
+    material::{
+        Conductor, Dieletric, DiffuseLight, Isotropic, Lambertian, Material, Metal, Rgb,
+        METAL_NAMES,
+    },
+    medium::ConstantMedium,
+    obj::{self, ObjError},
+    random::RandomGenerator,
+    shape::{Capsule, Cone, Cuboid, Cylinder, Disk, Plane, Quad, Sphere, Torus, Triangle},
+    texture::{Checker, ImageTexture, Marble, Noise, Texture, UvGrid, Voronoi, Wood},
+    tonemap::{ToneMapper, ToneMapping},
+    vec3::{AnimatedTransform, Point, Transform, Vec3},
+};
+
+/// Errors of loading a scene file. `key` is the path of the offending entry, like
+/// `shapes[3].radius`.
+#[derive(Debug)]
+pub enum SceneError {
+    Io { path: PathBuf, source: io::Error },
+    Parse { key: String, message: String },
+    Invalid { key: String, message: String },
+    Obj { key: String, source: ObjError },
+    Image { key: String, source: ImageError },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Parse { key, message } | SceneError::Invalid { key, message } => {
+                write!(f, "`{}`: {}", key, message)
+            }
+            SceneError::Obj { key, source } => write!(f, "`{}`: {}", key, source),
+            SceneError::Image { key, source } => write!(f, "`{}`: {}", key, source),
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Obj { source, .. } => Some(source),
+            SceneError::Image { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+fn invalid(key: impl Into<String>, message: impl Into<String>) -> SceneError {
+    SceneError::Invalid {
+        key: key.into(),
+        message: message.into(),
+    }
+}
+
+// the file format, every camera key is optional and falls back to `CameraConfig::default()`.
+// Textures, materials and shapes are kept as raw tables and decoded one by one with `parse_tagged`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    #[serde(default)]
+    camera: CameraSection,
+    background: Option<toml::Value>,
+    integrator: Option<toml::Value>,
+    #[serde(default)]
+    textures: BTreeMap<String, toml::Value>,
+    #[serde(default)]
+    materials: BTreeMap<String, toml::Value>,
+    #[serde(default)]
+    shapes: Vec<toml::Value>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CameraSection {
+    aspect_ratio: Option<f64>,
+    width: Option<usize>,
+    samples_per_pixel: Option<usize>,
+    max_depth: Option<usize>,
+    float_correction: Option<f64>,
+    vfov: Option<f64>,
+    look_from: Option<[f64; 3]>,
+    look_at: Option<[f64; 3]>,
+    camera_vup: Option<[f64; 3]>,
+    defocus_angle: Option<f64>,
+    focus_dist: Option<f64>,
+    shutter_open: Option<f64>,
+    shutter_close: Option<f64>,
+    save_path: Option<String>,
+    hdr_save_path: Option<String>,
+    exposure: Option<f64>,
+    tone_mapper: Option<ToneMapper>,
+    white_point: Option<f64>,
+    threads: Option<usize>,
+    seed: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TextureSection {
+    Checker {
+        #[serde(default = "default_scale")]
+        scale: f64,
+        even: [f64; 3],
+        odd: [f64; 3],
+    },
+    // 8-bit images are taken as sRGB, .hdr and .exr as linear
+    Image {
+        path: String,
+    },
+    // the procedural ones fall back to the defaults of their `new` for missing keys
+    Noise {
+        seed: Option<u64>,
+        frequency: Option<f64>,
+        octaves: Option<u32>,
+        color: Option<[f64; 3]>,
+    },
+    Marble {
+        seed: Option<u64>,
+        frequency: Option<f64>,
+        octaves: Option<u32>,
+        light: Option<[f64; 3]>,
+        dark: Option<[f64; 3]>,
+    },
+    Wood {
+        seed: Option<u64>,
+        frequency: Option<f64>,
+        octaves: Option<u32>,
+        light: Option<[f64; 3]>,
+        dark: Option<[f64; 3]>,
+    },
+    Voronoi {
+        seed: Option<u64>,
+        frequency: Option<f64>,
+        octaves: Option<u32>,
+        center: Option<[f64; 3]>,
+        edge: Option<[f64; 3]>,
+    },
+    UvGrid {
+        frequency: Option<f64>,
+        octaves: Option<u32>,
+    },
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+// either a plain color or the name of an entry of `[textures]`
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "expected an [r, g, b] color or a texture name")]
+enum Albedo {
+    Color([f64; 3]),
+    Texture(String),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialSection {
+    Lambertian {
+        albedo: Albedo,
+    },
+    Metal {
+        albedo: Albedo,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    // a GGX metal, either a preset `metal` or its complex index of refraction `eta` and `k`
+    Conductor {
+        metal: Option<String>,
+        eta: Option<[f64; 3]>,
+        k: Option<[f64; 3]>,
+        #[serde(default)]
+        roughness: f64,
+    },
+    #[serde(alias = "dieletric")]
+    Dielectric {
+        ir: f64,
+        #[serde(default)]
+        roughness: f64,
+    },
+    DiffuseLight {
+        emit: [f64; 3],
+    },
+    // the phase function of fog, see the shapes' `density`
+    Isotropic {
+        albedo: Albedo,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum BackgroundSection {
+    Sky {},
+    Black {},
+    Solid {
+        color: [f64; 3],
+    },
+    Gradient {
+        bottom: [f64; 3],
+        top: [f64; 3],
+        up: Option<[f64; 3]>,
+    },
+    // equirectangular .hdr or .exr panorama, `rotation` in degrees around the up axis
+    Environment {
+        path: String,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+        #[serde(default)]
+        rotation: f64,
+    },
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum IntegratorSection {
+    Path {
+        light_sampling: Option<LightSampling>,
+    },
+    Whitted {},
+    Normals {},
+    // `max_distance` is black, the camera is white
+    Depth {
+        max_distance: Option<f64>,
+    },
+    Albedo {},
+    // occluders further than `distance` don't darken the surface
+    AmbientOcclusion {
+        distance: Option<f64>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum ShapeSection {
+    Sphere {
+        center: [f64; 3],
+        // where the center is at time 1, for motion blur
+        center_end: Option<[f64; 3]>,
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        a: [f64; 3],
+        b: [f64; 3],
+        c: [f64; 3],
+        material: String,
+    },
+    // parallelogram with a corner and two edges
+    Quad {
+        corner: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: String,
+    },
+    Disk {
+        center: [f64; 3],
+        normal: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Plane {
+        point: [f64; 3],
+        normal: [f64; 3],
+        material: String,
+    },
+    // axis-aligned, between two opposite corners
+    Box {
+        a: [f64; 3],
+        b: [f64; 3],
+        material: String,
+    },
+    Cylinder {
+        base: [f64; 3],
+        top: [f64; 3],
+        radius: f64,
+        #[serde(default = "default_capped")]
+        capped: bool,
+        material: String,
+    },
+    // a frustum when `top_radius` isn't zero
+    Cone {
+        base: [f64; 3],
+        base_radius: f64,
+        top: [f64; 3],
+        #[serde(default)]
+        top_radius: f64,
+        #[serde(default = "default_capped")]
+        capped: bool,
+        material: String,
+    },
+    Capsule {
+        a: [f64; 3],
+        b: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Torus {
+        center: [f64; 3],
+        axis: Option<[f64; 3]>,
+        major_radius: f64,
+        minor_radius: f64,
+        material: String,
+    },
+    // `material` is used for the faces without an MTL material
+    Obj {
+        path: String,
+        material: Option<String>,
+    },
+    // another copy of the shape called `of`, sharing its geometry
+    Instance {
+        of: String,
+    },
+}
+
+// the keys every shape can have, taken out before parsing the shape itself
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ShapeOptions {
+    // for instances to refer to
+    name: Option<String>,
+    transform: Option<Vec<TransformStep>>,
+    // the pose at time 1, when the shape moves during the shutter interval
+    transform_end: Option<Vec<TransformStep>>,
+    // fills the shape with fog, its material is the phase function
+    density: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TransformStep {
+    Translate([f64; 3]),
+    Scale([f64; 3]),
+    Rotate { axis: [f64; 3], angle: f64 },
+}
+
+fn default_capped() -> bool {
+    true
+}
+
+fn parse_options(key: &str, table: &mut toml::Table) -> Result<ShapeOptions, SceneError> {
+    let mut options = toml::Table::new();
+    for option in ["name", "transform", "transform_end", "density"] {
+        if let Some(value) = table.remove(option) {
+            options.insert(option.to_string(), value);
+        }
+    }
+    serde_path_to_error::deserialize(toml::Value::Table(options))
+        .map_err(|err| parse_error(format!("{}.{}", key, err.path()), err.inner().message()))
+}
+
+// the steps are applied in order
+fn to_transform(key: &str, steps: Vec<TransformStep>) -> Result<Transform, SceneError> {
+    let mut transform = Transform::identity();
+    for (ix, step) in steps.into_iter().enumerate() {
+        let key = format!("{}[{}]", key, ix);
+        let step = match step {
+            TransformStep::Translate(offset) => Transform::translation(point(offset)),
+            TransformStep::Scale(factors) => {
+                if factors.iter().any(|&f| f == 0.0 || !f.is_finite()) {
+                    return Err(invalid(key + ".scale", "must not be zero"));
+                }
+                Transform::scaling(point(factors))
+            }
+            TransformStep::Rotate { axis, angle } => {
+                Transform::rotation(direction(key + ".rotate.axis", axis)?, angle)
+            }
+        };
+        transform = transform.then(&step);
+    }
+    Ok(transform)
+}
+
+fn parse_error(key: impl Into<String>, message: impl Into<String>) -> SceneError {
+    SceneError::Parse {
+        key: key.into(),
+        message: message.into(),
+    }
+}
+
+// decodes a table like `{ type = "sphere", radius = 1.0 }` into the matching variant of `T`.
+// serde's internally tagged enums lose the path of the failing field, so the `type` key is
+// turned into an external tag and the error path rebased onto `key` instead.
+fn parse_tagged<T: DeserializeOwned>(key: &str, value: toml::Value) -> Result<T, SceneError> {
+    let toml::Value::Table(mut table) = value else {
+        return Err(parse_error(key, "expected a table"));
+    };
+    let variant = match table.remove("type") {
+        Some(toml::Value::String(variant)) => variant,
+        Some(_) => return Err(parse_error(format!("{}.type", key), "expected a string")),
+        None => return Err(parse_error(key, "missing field `type`")),
+    };
+
+    let mut tagged = toml::Table::new();
+    tagged.insert(variant, toml::Value::Table(table));
+    serde_path_to_error::deserialize(toml::Value::Table(tagged)).map_err(|err| {
+        let path = err.path().to_string();
+        // the path is `.` when the variant itself is unknown, `variant.field...` otherwise
+        let key = match path.split_once('.') {
+            _ if path == "." => format!("{}.type", key),
+            Some((_, field)) => format!("{}.{}", key, field),
+            None => key.to_string(),
+        };
+        parse_error(key, err.inner().message())
+    })
+}
+
+fn point(p: [f64; 3]) -> Point {
+    Point::new(p[0], p[1], p[2])
+}
+
+fn rgb(c: [f64; 3]) -> Rgb {
+    Rgb::new(c[0], c[1], c[2])
+}
+
+// a vector that has to point somewhere, like a normal
+fn direction(key: String, v: [f64; 3]) -> Result<Vec3, SceneError> {
+    let v = point(v);
+    if v.length() == 0.0 || v.length().is_nan() {
+        return Err(invalid(key, "must not be a zero vector"));
+    }
+    Ok(v)
+}
+
+/// A world and the camera looking at it, either built in code or loaded from a TOML file.
+pub struct Scene {
+    // the output paths of this config are ignored, they're taken from the fields below
+    pub camera: CameraConfig<'static>,
+    pub save_path: String,
+    pub hdr_save_path: Option<String>,
+    pub world: HittableList,
+    // the emissive shapes of `world` that can be sampled, see `Camera::render`
+    pub lights: HittableList,
+}
+
+impl Scene {
+    pub fn load(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Scene::parse(&source, path.parent().unwrap_or(Path::new(".")))
+    }
+
+    /// Parses a scene from TOML, relative paths inside it are resolved against `dir`.
+    pub fn parse(source: &str, dir: &Path) -> Result<Scene, SceneError> {
+        let file: SceneFile = serde_path_to_error::deserialize(toml::Deserializer::new(source))
+            .map_err(|err| {
+                let path = err.path().to_string();
+                // syntax errors have no key, point at the line instead
+                let key = match err.inner().span() {
+                    Some(span) if path == "." => {
+                        format!("line {}", source[..span.start].lines().count().max(1))
+                    }
+                    _ => path,
+                };
+                parse_error(key, err.inner().message())
+            })?;
+
+        let hdr_save_path = file.camera.hdr_save_path.clone();
+        let (mut camera, save_path) = file.camera.into_config()?;
+        if let Some(value) = file.background {
+            let section: BackgroundSection = parse_tagged("background", value)?;
+            camera.background = section.to_background(dir)?;
+        }
+        if let Some(value) = file.integrator {
+            let section: IntegratorSection = parse_tagged("integrator", value)?;
+            camera.integrator = section.to_integrator()?;
+        }
+
+        let mut textures: HashMap<String, Arc<dyn Texture>> = HashMap::new();
+        for (name, value) in file.textures {
+            let key = format!("textures.{}", name);
+            let section: TextureSection = parse_tagged(&key, value)?;
+            textures.insert(name, section.to_texture(&key, dir)?);
+        }
+
+        let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+        let mut emissive: HashSet<String> = HashSet::new();
+        for (name, value) in file.materials {
+            let key = format!("materials.{}", name);
+            let section: MaterialSection = parse_tagged(&key, value)?;
+            if let MaterialSection::DiffuseLight { .. } = section {
+                emissive.insert(name.clone());
+            }
+            materials.insert(name, section.to_material(&key, &textures)?);
+        }
+        let lookup = |key: String, name: &str| {
+            materials
+                .get(name)
+                .cloned()
+                .ok_or_else(|| invalid(key, format!("unknown material `{}`", name)))
+        };
+
+        let mut world = HittableList::new();
+        let mut lights = HittableList::new();
+        // the objects of each named shape, and whether they're lights
+        let mut named: HashMap<String, (HittableList, bool)> = HashMap::new();
+        for (ix, mut value) in file.shapes.into_iter().enumerate() {
+            let key = format!("shapes[{}]", ix);
+            let (options, phase) = match &mut value {
+                toml::Value::Table(table) => {
+                    let phase = table.get("material").cloned();
+                    (parse_options(&key, table)?, phase)
+                }
+                _ => (ShapeOptions::default(), None),
+            };
+            if let Some(name) = options
+                .name
+                .as_ref()
+                .filter(|name| named.contains_key(*name))
+            {
+                return Err(invalid(
+                    key + ".name",
+                    format!("duplicate shape `{}`", name),
+                ));
+            }
+            let transform = options
+                .transform
+                .map(|steps| to_transform(&format!("{}.transform", key), steps))
+                .transpose()?;
+            let transform_end = options
+                .transform_end
+                .map(|steps| to_transform(&format!("{}.transform_end", key), steps))
+                .transpose()?;
+            let motion = match (transform, transform_end) {
+                (None, None) => None,
+                (start, end) => {
+                    let start = start.unwrap_or_else(Transform::identity);
+                    Some(AnimatedTransform::new(start, end.unwrap_or(start)))
+                }
+            };
+            // the path tracer aims at the shapes made of a light, but not at fog glowing inside
+            let mut is_light = options.density.is_none()
+                && matches!(&phase, Some(toml::Value::String(name)) if emissive.contains(name));
+            let medium = match (options.density, phase) {
+                (None, _) => None,
+                (Some(density), _) if density.is_nan() || density <= 0.0 => {
+                    return Err(invalid(key + ".density", "must be positive"));
+                }
+                (Some(density), Some(toml::Value::String(phase))) => {
+                    Some((density, lookup(format!("{}.material", key), &phase)?))
+                }
+                (Some(_), _) => {
+                    return Err(invalid(key + ".density", "needs the shape's material"));
+                }
+            };
+
+            let mut objects = HittableList::new();
+            match parse_tagged(&key, value)? {
+                ShapeSection::Sphere {
+                    center,
+                    center_end,
+                    radius,
+                    material,
+                } => {
+                    if radius.is_nan() || radius <= 0.0 {
+                        return Err(invalid(key + ".radius", "must be positive"));
+                    }
+                    let material = lookup(key + ".material", &material)?;
+                    let end = point(center_end.unwrap_or(center));
+                    objects.push(Arc::new(Sphere::moving(
+                        point(center),
+                        end,
+                        radius,
+                        material,
+                    )));
+                }
+                ShapeSection::Triangle { a, b, c, material } => {
+                    objects.push(Arc::new(Triangle {
+                        a: point(a),
+                        b: point(b),
+                        c: point(c),
+                        material: lookup(key + ".material", &material)?,
+                    }));
+                }
+                ShapeSection::Quad {
+                    corner,
+                    u,
+                    v,
+                    material,
+                } => {
+                    let (u, v) = (point(u), point(v));
+                    if u.cross(v).length() == 0.0 {
+                        return Err(invalid(key + ".v", "must not be parallel to u"));
+                    }
+                    let material = lookup(key + ".material", &material)?;
+                    objects.push(Arc::new(Quad::new(point(corner), u, v, material)));
+                }
+                ShapeSection::Disk {
+                    center,
+                    normal,
+                    radius,
+                    material,
+                } => {
+                    if radius.is_nan() || radius <= 0.0 {
+                        return Err(invalid(key + ".radius", "must be positive"));
+                    }
+                    let normal = direction(format!("{}.normal", key), normal)?;
+                    let material = lookup(key + ".material", &material)?;
+                    objects.push(Arc::new(Disk::new(point(center), normal, radius, material)));
+                }
+                ShapeSection::Plane {
+                    point: p,
+                    normal,
+                    material,
+                } => {
+                    let normal = direction(format!("{}.normal", key), normal)?;
+                    let material = lookup(key + ".material", &material)?;
+                    objects.push(Arc::new(Plane::new(point(p), normal, material)));
+                }
+                ShapeSection::Box { a, b, material } => {
+                    let material = lookup(key + ".material", &material)?;
+                    objects.push(Arc::new(Cuboid::new(point(a), point(b), material)));
+                }
+                ShapeSection::Cylinder {
+                    base,
+                    top,
+                    radius,
+                    capped,
+                    material,
+                } => {
+                    if radius.is_nan() || radius <= 0.0 {
+                        return Err(invalid(key + ".radius", "must be positive"));
+                    }
+                    if base == top {
+                        return Err(invalid(key + ".top", "must differ from base"));
+                    }
+                    let material = lookup(key + ".material", &material)?;
+                    objects.push(Arc::new(Cylinder::new(
+                        point(base),
+                        point(top),
+                        radius,
+                        capped,
+                        material,
+                    )));
+                }
+                ShapeSection::Cone {
+                    base,
+                    base_radius,
+                    top,
+                    top_radius,
+                    capped,
+                    material,
+                } => {
+                    if base_radius.is_nan() || base_radius <= 0.0 {
+                        return Err(invalid(key + ".base_radius", "must be positive"));
+                    }
+                    if top_radius.is_nan() || top_radius < 0.0 {
+                        return Err(invalid(key + ".top_radius", "must not be negative"));
+                    }
+                    if base == top {
+                        return Err(invalid(key + ".top", "must differ from base"));
+                    }
+                    let material = lookup(key + ".material", &material)?;
+                    objects.push(Arc::new(Cone::new(
+                        point(base),
+                        base_radius,
+                        point(top),
+                        top_radius,
+                        capped,
+                        material,
+                    )));
+                }
+                ShapeSection::Capsule {
+                    a,
+                    b,
+                    radius,
+                    material,
+                } => {
+                    if radius.is_nan() || radius <= 0.0 {
+                        return Err(invalid(key + ".radius", "must be positive"));
+                    }
+                    if a == b {
+                        return Err(invalid(key + ".b", "must differ from a"));
+                    }
+                    let material = lookup(key + ".material", &material)?;
+                    objects.push(Arc::new(Capsule::new(point(a), point(b), radius, material)));
+                }
+                ShapeSection::Torus {
+                    center,
+                    axis,
+                    major_radius,
+                    minor_radius,
+                    material,
+                } => {
+                    if major_radius.is_nan() || major_radius <= 0.0 {
+                        return Err(invalid(key + ".major_radius", "must be positive"));
+                    }
+                    if minor_radius.is_nan() || minor_radius <= 0.0 {
+                        return Err(invalid(key + ".minor_radius", "must be positive"));
+                    }
+                    let axis = direction(format!("{}.axis", key), axis.unwrap_or([0.0, 1.0, 0.0]))?;
+                    let material = lookup(key + ".material", &material)?;
+                    objects.push(Arc::new(Torus::new(
+                        point(center),
+                        axis,
+                        major_radius,
+                        minor_radius,
+                        material,
+                    )));
+                }
+                ShapeSection::Obj { path, material } => {
+                    let default_material = match material {
+                        Some(name) => lookup(format!("{}.material", key), &name)?,
+                        None => Arc::new(Lambertian::new(Rgb::new(0.8, 0.8, 0.8))),
+                    };
+                    let meshes =
+                        obj::load_obj(dir.join(path), default_material).map_err(|source| {
+                            SceneError::Obj {
+                                key: key + ".path",
+                                source,
+                            }
+                        })?;
+                    objects.extend(meshes);
+                    // meshes can't pick points on themselves
+                    is_light = false;
+                }
+                ShapeSection::Instance { of } => {
+                    let (shape, light) = named
+                        .get(&of)
+                        .ok_or_else(|| invalid(key + ".of", format!("unknown shape `{}`", of)))?;
+                    objects.extend(shape.iter().cloned());
+                    is_light = *light && options.density.is_none();
+                }
+            }
+
+            if let Some(motion) = motion {
+                objects = objects
+                    .into_iter()
+                    .map(|object| {
+                        Arc::new(Transformed::animated(object, motion)) as Arc<dyn Hittable>
+                    })
+                    .collect();
+            }
+            if let Some((density, phase)) = medium {
+                objects = objects
+                    .into_iter()
+                    .map(|object| {
+                        Arc::new(ConstantMedium::new(object, density, phase.clone()))
+                            as Arc<dyn Hittable>
+                    })
+                    .collect();
+            }
+            if let Some(name) = options.name {
+                named.insert(name, (objects.clone(), is_light));
+            }
+            if is_light {
+                lights.extend(objects.iter().cloned());
+            }
+            world.extend(objects);
+        }
+
+        Ok(Scene {
+            camera,
+            save_path,
+            hdr_save_path,
+            world,
+            lights,
+        })
+    }
+
+    pub fn camera_config(&self) -> CameraConfig<'_> {
+        CameraConfig {
+            save_path: &self.save_path,
+            hdr_save_path: self.hdr_save_path.as_deref(),
+            ..self.camera.clone()
+        }
+    }
+}
+
+impl CameraSection {
+    fn into_config(self) -> Result<(CameraConfig<'static>, String), SceneError> {
+        let default = CameraConfig::default();
+        let config = CameraConfig {
+            aspect_ratio: self.aspect_ratio.unwrap_or(default.aspect_ratio),
+            width: self.width.unwrap_or(default.width),
+            samples_per_pixel: self.samples_per_pixel.unwrap_or(default.samples_per_pixel),
+            max_depth: self.max_depth.unwrap_or(default.max_depth),
+            float_correction: self.float_correction.unwrap_or(default.float_correction),
+            vfov: self.vfov.unwrap_or(default.vfov),
+            look_from: self.look_from.map_or(default.look_from, point),
+            look_at: self.look_at.map_or(default.look_at, point),
+            camera_vup: self.camera_vup.map_or(default.camera_vup, point),
+            defocus_angle: self.defocus_angle.unwrap_or(default.defocus_angle),
+            focus_dist: self.focus_dist.unwrap_or(default.focus_dist),
+            shutter_open: self.shutter_open.unwrap_or(default.shutter_open),
+            shutter_close: self.shutter_close.unwrap_or(default.shutter_close),
+            threads: self.threads.unwrap_or(default.threads),
+            seed: self.seed.unwrap_or(default.seed),
+            tone_mapping: ToneMapping {
+                exposure: self.exposure.unwrap_or(default.tone_mapping.exposure),
+                tone_mapper: self.tone_mapper.unwrap_or(default.tone_mapping.tone_mapper),
+                white_point: self.white_point.unwrap_or(default.tone_mapping.white_point),
+            },
+            ..default
+        };
+        let save_path = self
+            .save_path
+            .unwrap_or_else(|| default.save_path.to_string());
+
+        validate_camera(&config)?;
+        Ok((config, save_path))
+    }
+}
+
+/// Checks the values that would make `Camera::create` produce garbage, `key` names the field.
+pub fn validate_camera(config: &CameraConfig) -> Result<(), SceneError> {
+    if config.aspect_ratio.is_nan() || config.aspect_ratio <= 0.0 {
+        return Err(invalid("camera.aspect_ratio", "must be positive"));
+    }
+    if config.width == 0 {
+        return Err(invalid("camera.width", "must be at least 1"));
+    }
+    if config.samples_per_pixel == 0 {
+        return Err(invalid("camera.samples_per_pixel", "must be at least 1"));
+    }
+    if config.max_depth == 0 {
+        return Err(invalid("camera.max_depth", "must be at least 1"));
+    }
+    if !(config.vfov > 0.0 && config.vfov < 180.0) {
+        return Err(invalid("camera.vfov", "must be between 0 and 180 degrees"));
+    }
+    if config.focus_dist.is_nan() || config.focus_dist <= 0.0 {
+        return Err(invalid("camera.focus_dist", "must be positive"));
+    }
+    if !config.tone_mapping.exposure.is_finite() {
+        return Err(invalid("camera.exposure", "must be a finite number"));
+    }
+    if config.tone_mapping.white_point.is_nan() || config.tone_mapping.white_point <= 0.0 {
+        return Err(invalid("camera.white_point", "must be positive"));
+    }
+    if (config.look_from - config.look_at).length() == 0.0 {
+        return Err(invalid("camera.look_at", "must differ from look_from"));
+    }
+    if !config.shutter_open.is_finite() {
+        return Err(invalid("camera.shutter_open", "must be a finite number"));
+    }
+    if !(config.shutter_close.is_finite() && config.shutter_close >= config.shutter_open) {
+        return Err(invalid(
+            "camera.shutter_close",
+            "must not be before shutter_open",
+        ));
+    }
+    Ok(())
+}
+
+impl BackgroundSection {
+    fn to_background(&self, dir: &Path) -> Result<Arc<dyn Background>, SceneError> {
+        Ok(match self {
+            BackgroundSection::Sky {} => Arc::new(Gradient::sky()),
+            BackgroundSection::Black {} => Arc::new(Rgb::new(0.0, 0.0, 0.0)),
+            BackgroundSection::Solid { color } => Arc::new(rgb(*color)),
+            BackgroundSection::Gradient { bottom, top, up } => {
+                let up = up.map_or(Vec3::new(0.0, 1.0, 0.0), point);
+                if up.length() == 0.0 {
+                    return Err(invalid("background.up", "must not be a zero vector"));
+                }
+                Arc::new(Gradient {
+                    bottom: rgb(*bottom),
+                    top: rgb(*top),
+                    up,
+                })
+            }
+            BackgroundSection::Environment {
+                path,
+                intensity,
+                rotation,
+            } => {
+                if intensity.is_nan() || *intensity < 0.0 {
+                    return Err(invalid("background.intensity", "must not be negative"));
+                }
+                let mut map = EnvironmentMap::load(dir.join(path))
+                    .map_err(|source| SceneError::Image {
+                        key: "background.path".to_string(),
+                        source,
+                    })?
+                    .with_rotation(*rotation);
+                map.intensity = *intensity;
+                Arc::new(map)
+            }
+        })
+    }
+}
+
+impl IntegratorSection {
+    fn to_integrator(&self) -> Result<Arc<dyn Integrator>, SceneError> {
+        let positive = |key: &str, value: Option<f64>, default: f64| match value {
+            Some(x) if x.is_nan() || x <= 0.0 => Err(invalid(key, "must be positive")),
+            x => Ok(x.unwrap_or(default)),
+        };
+        Ok(match self {
+            IntegratorSection::Path { light_sampling } => {
+                let default = PathTracer::default();
+                Arc::new(PathTracer {
+                    light_sampling: light_sampling.unwrap_or(default.light_sampling),
+                })
+            }
+            IntegratorSection::Whitted {} => Arc::new(Whitted),
+            IntegratorSection::Normals {} => Arc::new(Normals),
+            IntegratorSection::Depth { max_distance } => Arc::new(Depth {
+                max_distance: positive(
+                    "integrator.max_distance",
+                    *max_distance,
+                    Depth::default().max_distance,
+                )?,
+            }),
+            IntegratorSection::Albedo {} => Arc::new(integrator::Albedo),
+            IntegratorSection::AmbientOcclusion { distance } => Arc::new(AmbientOcclusion {
+                distance: positive(
+                    "integrator.distance",
+                    *distance,
+                    AmbientOcclusion::default().distance,
+                )?,
+            }),
+        })
+    }
+}
+
+// checks the optional frequency and octaves of a procedural texture, `defaults` fill the gaps
+fn frequency_octaves(
+    key: &str,
+    frequency: Option<f64>,
+    octaves: Option<u32>,
+    defaults: (f64, u32),
+) -> Result<(f64, u32), SceneError> {
+    let frequency = frequency.unwrap_or(defaults.0);
+    if frequency.is_nan() || frequency <= 0.0 {
+        return Err(invalid(format!("{}.frequency", key), "must be positive"));
+    }
+    let octaves = octaves.unwrap_or(defaults.1);
+    if octaves == 0 {
+        return Err(invalid(format!("{}.octaves", key), "must be at least 1"));
+    }
+    Ok((frequency, octaves))
+}
+
+impl TextureSection {
+    fn to_texture(&self, key: &str, dir: &Path) -> Result<Arc<dyn Texture>, SceneError> {
+        Ok(match self {
+            TextureSection::Checker { scale, even, odd } => {
+                if scale.is_nan() || *scale <= 0.0 {
+                    return Err(invalid(format!("{}.scale", key), "must be positive"));
+                }
+                Arc::new(Checker::new(*scale, rgb(*even), rgb(*odd)))
+            }
+            TextureSection::Image { path } => {
+                let texture =
+                    ImageTexture::load(dir.join(path)).map_err(|source| SceneError::Image {
+                        key: format!("{}.path", key),
+                        source,
+                    })?;
+                Arc::new(texture)
+            }
+            TextureSection::Noise {
+                seed,
+                frequency,
+                octaves,
+                color,
+            } => {
+                let mut texture = Noise::new(seed.unwrap_or(0));
+                (texture.frequency, texture.octaves) = frequency_octaves(
+                    key,
+                    *frequency,
+                    *octaves,
+                    (texture.frequency, texture.octaves),
+                )?;
+                texture.color = color.map_or(texture.color, rgb);
+                Arc::new(texture)
+            }
+            TextureSection::Marble {
+                seed,
+                frequency,
+                octaves,
+                light,
+                dark,
+            } => {
+                let mut texture = Marble::new(seed.unwrap_or(0));
+                (texture.frequency, texture.octaves) = frequency_octaves(
+                    key,
+                    *frequency,
+                    *octaves,
+                    (texture.frequency, texture.octaves),
+                )?;
+                texture.light = light.map_or(texture.light, rgb);
+                texture.dark = dark.map_or(texture.dark, rgb);
+                Arc::new(texture)
+            }
+            TextureSection::Wood {
+                seed,
+                frequency,
+                octaves,
+                light,
+                dark,
+            } => {
+                let mut texture = Wood::new(seed.unwrap_or(0));
+                (texture.frequency, texture.octaves) = frequency_octaves(
+                    key,
+                    *frequency,
+                    *octaves,
+                    (texture.frequency, texture.octaves),
+                )?;
+                texture.light = light.map_or(texture.light, rgb);
+                texture.dark = dark.map_or(texture.dark, rgb);
+                Arc::new(texture)
+            }
+            TextureSection::Voronoi {
+                seed,
+                frequency,
+                octaves,
+                center,
+                edge,
+            } => {
+                let mut texture = Voronoi::new(seed.unwrap_or(0));
+                (texture.frequency, texture.octaves) = frequency_octaves(
+                    key,
+                    *frequency,
+                    *octaves,
+                    (texture.frequency, texture.octaves),
+                )?;
+                texture.center = center.map_or(texture.center, rgb);
+                texture.edge = edge.map_or(texture.edge, rgb);
+                Arc::new(texture)
+            }
+            TextureSection::UvGrid { frequency, octaves } => {
+                let defaults = UvGrid::default();
+                let (frequency, octaves) = frequency_octaves(
+                    key,
+                    *frequency,
+                    *octaves,
+                    (defaults.frequency, defaults.octaves),
+                )?;
+                Arc::new(UvGrid { frequency, octaves })
+            }
+        })
+    }
+}
+
+impl Albedo {
+    fn to_texture(
+        &self,
+        key: &str,
+        textures: &HashMap<String, Arc<dyn Texture>>,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        match self {
+            Albedo::Color(color) => Ok(Arc::new(rgb(*color))),
+            Albedo::Texture(name) => textures
+                .get(name)
+                .cloned()
+                .ok_or_else(|| invalid(key, format!("unknown texture `{}`", name))),
+        }
+    }
+}
+
+impl MaterialSection {
+    fn to_material(
+        &self,
+        key: &str,
+        textures: &HashMap<String, Arc<dyn Texture>>,
+    ) -> Result<Arc<dyn Material>, SceneError> {
+        Ok(match self {
+            MaterialSection::Lambertian { albedo } => Arc::new(Lambertian {
+                albedo: albedo.to_texture(&format!("{}.albedo", key), textures)?,
+            }),
+            MaterialSection::Metal { albedo, fuzz } => {
+                if !(0.0..=1.0).contains(fuzz) {
+                    return Err(invalid(format!("{}.fuzz", key), "must be between 0 and 1"));
+                }
+                Arc::new(Metal {
+                    albedo: albedo.to_texture(&format!("{}.albedo", key), textures)?,
+                    fuzz: *fuzz,
+                })
+            }
+            MaterialSection::Conductor {
+                metal,
+                eta,
+                k,
+                roughness,
+            } => {
+                if !(0.0..=1.0).contains(roughness) {
+                    return Err(invalid(
+                        format!("{}.roughness", key),
+                        "must be between 0 and 1",
+                    ));
+                }
+                let conductor = match (metal, eta, k) {
+                    (Some(metal), None, None) => {
+                        Conductor::preset(metal, *roughness).ok_or_else(|| {
+                            invalid(
+                                format!("{}.metal", key),
+                                format!(
+                                    "unknown metal `{}`, expected one of {}",
+                                    metal,
+                                    METAL_NAMES.join(", ")
+                                ),
+                            )
+                        })?
+                    }
+                    (None, Some(eta), Some(k)) => {
+                        if eta.iter().chain(k).any(|c| c.is_nan() || *c < 0.0) {
+                            return Err(invalid(
+                                format!("{}.eta", key),
+                                "eta and k must not be negative",
+                            ));
+                        }
+                        Conductor::new(rgb(*eta), rgb(*k), *roughness)
+                    }
+                    (Some(_), _, _) => {
+                        return Err(invalid(
+                            format!("{}.metal", key),
+                            "can't be combined with eta and k",
+                        ))
+                    }
+                    (None, _, _) => {
+                        return Err(invalid(
+                            format!("{}.eta", key),
+                            "expected both eta and k, or a metal",
+                        ))
+                    }
+                };
+                Arc::new(conductor)
+            }
+            MaterialSection::Dielectric { ir, roughness } => {
+                if ir.is_nan() || *ir <= 0.0 {
+                    return Err(invalid(format!("{}.ir", key), "must be positive"));
+                }
+                if !(0.0..=1.0).contains(roughness) {
+                    return Err(invalid(
+                        format!("{}.roughness", key),
+                        "must be between 0 and 1",
+                    ));
+                }
+                Arc::new(Dieletric::new(*ir, *roughness))
+            }
+            MaterialSection::DiffuseLight { emit } => {
+                if emit.iter().any(|c| c.is_nan() || *c < 0.0) {
+                    return Err(invalid(format!("{}.emit", key), "must not be negative"));
+                }
+                Arc::new(DiffuseLight { emit: rgb(*emit) })
+            }
+            MaterialSection::Isotropic { albedo } => Arc::new(Isotropic {
+                albedo: albedo.to_texture(&format!("{}.albedo", key), textures)?,
+            }),
+        })
+    }
+}
+
+/// Names accepted by `builtin`.
+pub const BUILTIN_SCENES: [&str; 2] = ["random_spheres", "procedural_spheres"];
+
+/// The scenes built in code, `None` when `name` isn't one of `BUILTIN_SCENES`.
+pub fn builtin(name: &str) -> Option<Scene> {
+    match name {
+        "random_spheres" => Some(random_spheres(2023)),
+        "procedural_spheres" => Some(procedural_spheres(2023)),
+        _ => None,
+    }
+}
+
+/// The final scene of the book: a few hundred small random spheres around three big ones.
+pub fn random_spheres(seed: u64) -> Scene {
+    sphere_field(seed, false)
+}
+
+/// `random_spheres` dressed with procedural textures: marble, wood, cells and smoke on the
+/// diffuse spheres, wood and stone on the big ones.
+pub fn procedural_spheres(seed: u64) -> Scene {
+    sphere_field(seed, true)
+}
+
+// a random color, the product of two keeps most of them dark and saturated
+fn random_albedo(random_generator: &mut RandomGenerator) -> Rgb {
+    let mut random_rgb = || {
+        Rgb::new(
+            random_generator.gen_range(0.0..1.0),
+            random_generator.gen_range(0.0..1.0),
+            random_generator.gen_range(0.0..1.0),
+        )
+    };
+    random_rgb() * random_rgb()
+}
+
+fn random_procedural(random_generator: &mut RandomGenerator) -> Arc<dyn Texture> {
+    let seed = random_generator.gen();
+    let frequency = random_generator.gen_range(2.0..10.0);
+    let octaves = random_generator.gen_range(1..8);
+    let light = Rgb::white() - 0.5 * random_albedo(random_generator);
+    let dark = random_albedo(random_generator);
+
+    match random_generator.gen_range(0..4) {
+        0 => Arc::new(Noise {
+            frequency,
+            octaves,
+            color: light,
+            ..Noise::new(seed)
+        }),
+        1 => Arc::new(Marble {
+            frequency,
+            octaves,
+            light,
+            dark,
+            ..Marble::new(seed)
+        }),
+        2 => Arc::new(Wood {
+            frequency: 4.0 * frequency,
+            octaves,
+            ..Wood::new(seed)
+        }),
+        _ => Arc::new(Voronoi {
+            frequency,
+            octaves: octaves.min(3),
+            center: dark,
+            edge: light,
+            ..Voronoi::new(seed)
+        }),
+    }
+}
+
+fn sphere_field(seed: u64, procedural: bool) -> Scene {
+    let mut random_generator = RandomGenerator::new(seed);
+
+    let mut world: HittableList = HittableList::new();
+    let ground_material = if procedural {
+        Arc::new(Lambertian::new(Voronoi {
+            frequency: 0.5,
+            octaves: 2,
+            center: Rgb::new(0.3, 0.3, 0.3),
+            edge: Rgb::new(0.6, 0.6, 0.55),
+            ..Voronoi::new(seed)
+        }))
+    } else {
+        Arc::new(Lambertian::new(Rgb::new(0.5, 0.5, 0.5)))
+    };
+    world.push(Arc::new(Plane::new(
+        Point::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        ground_material,
+    )));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = random_generator.gen_range(0.0..1.0);
+            let center = Point::new(
+                a as f64 + 0.9 * random_generator.gen_range(0.0..1.0),
+                0.2,
+                b as f64 + 0.9 * random_generator.gen_range(0.0..1.0),
+            );
+
+            if (center - Point::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                let sphere_material: Arc<dyn Material> = match choose_mat {
+                    a if a < 0.8 && procedural => Arc::new(Lambertian {
+                        albedo: random_procedural(&mut random_generator),
+                    }),
+                    a if a < 0.8 => Arc::new(Lambertian::new(random_albedo(&mut random_generator))),
+                    b if b < 0.95 => {
+                        let albedo = Rgb::new(
+                            random_generator.gen_range(0.5..1.0),
+                            random_generator.gen_range(0.5..1.0),
+                            random_generator.gen_range(0.5..1.0),
+                        );
+                        let fuzz = random_generator.gen_range(0.0..0.5);
+                        Arc::new(Metal::new(albedo, fuzz))
+                    }
+                    _ => Arc::new(Dieletric::new(1.5, 0.0)),
+                };
+                world.push(Arc::new(Sphere::new(center, 0.2, sphere_material)));
+            }
+        }
+    }
+
+    world.push(Arc::new(Sphere::new(
+        Point::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Dieletric::new(1.5, 0.0)),
+    )));
+    let brown: Arc<dyn Material> = if procedural {
+        Arc::new(Lambertian::new(Wood::new(seed)))
+    } else {
+        Arc::new(Lambertian::new(Rgb::new(0.4, 0.2, 0.1)))
+    };
+    world.push(Arc::new(Sphere::new(
+        Point::new(-5.0, 1.0, 0.0),
+        1.0,
+        brown,
+    )));
+    world.push(Arc::new(Sphere::new(
+        Point::new(4.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Metal::new(Rgb::new(0.7, 0.6, 0.5), 0.0)),
+    )));
+
+    Scene {
+        camera: CameraConfig {
+            width: 2560,
+            vfov: 20.0,
+            max_depth: 20,
+            samples_per_pixel: 100,
+            look_from: Point::new(13.0, 2.0, 3.0),
+            look_at: Point::new(0.0, 0.0, 0.0),
+            camera_vup: Vec3::new(0.0, 1.0, 0.0),
+            float_correction: 0.00001,
+            defocus_angle: 0.6,
+            focus_dist: 10.0,
+            aspect_ratio: 16.0 / 9.0,
+            seed,
+            ..Default::default()
+        },
+        save_path: "/tmp/pic.png".to_string(),
+        hdr_save_path: None,
+        world,
+        lights: HittableList::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aabb::Aabb;
+
+    fn parse(source: &str) -> Result<Scene, SceneError> {
+        Scene::parse(source, Path::new("."))
+    }
+
+    fn error_key(source: &str) -> String {
+        match parse(source).err().unwrap() {
+            SceneError::Parse { key, .. } | SceneError::Invalid { key, .. } => key,
+            SceneError::Obj { key, .. } | SceneError::Image { key, .. } => key,
+            SceneError::Io { .. } => panic!("unexpected io error"),
+        }
+    }
+
+    const SCENE: &str = r#"
+[camera]
+width = 400
+vfov = 30.0
+tone_mapper = "hable"
+look_from = [0.0, 1.0, 5.0]
+save_path = "/tmp/scene.png"
+
+[background]
+type = "solid"
+color = [0.0, 0.0, 0.5]
+
+[textures.tiles]
+type = "checker"
+scale = 0.5
+even = [1.0, 1.0, 1.0]
+odd = [0.0, 0.0, 0.0]
+
+[materials.red]
+type = "lambertian"
+albedo = [0.8, 0.1, 0.1]
+
+[materials.floor]
+type = "metal"
+albedo = "tiles"
+
+[materials.glass]
+type = "dieletric"
+ir = 1.5
+
+[materials.lamp]
+type = "diffuse_light"
+emit = [4.0, 4.0, 4.0]
+
+[[shapes]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "red"
+
+[[shapes]]
+type = "triangle"
+a = [0.0, 0.0, 0.0]
+b = [1.0, 0.0, 0.0]
+c = [0.0, 1.0, 0.0]
+material = "glass"
+"#;
+
+    #[test]
+    fn test_parse() {
+        let scene = parse(SCENE).unwrap();
+        let config = scene.camera_config();
+
+        assert!(scene.world.len() == 2);
+        assert!(config.width == 400 && config.vfov == 30.0);
+        assert!(config.look_from.j == 1.0 && config.look_from.k == 5.0);
+        assert!(config.samples_per_pixel == CameraConfig::default().samples_per_pixel);
+        assert!(config.save_path == "/tmp/scene.png");
+        assert!(config.tone_mapping.tone_mapper == ToneMapper::Hable);
+    }
+
+    #[test]
+    fn test_unknown_material() {
+        let source = SCENE.replace("material = \"glass\"", "material = \"steel\"");
+
+        assert!(error_key(&source) == "shapes[1].material");
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let source = SCENE.replace("radius = 1.0", "radius = \"big\"");
+
+        assert!(error_key(&source) == "shapes[0].radius");
+    }
+
+    #[test]
+    fn test_unknown_field() {
+        let source = SCENE.replace("width = 400", "widht = 400");
+
+        assert!(error_key(&source) == "camera.widht");
+
+        let source = SCENE.replace("radius = 1.0", "radus = 1.0");
+
+        assert!(error_key(&source) == "shapes[0].radus");
+
+        let source = SCENE.replace("type = \"sphere\"", "type = \"sphre\"");
+
+        assert!(error_key(&source) == "shapes[0].type");
+
+        let source = SCENE.replace("\"hable\"", "\"filmic\"");
+
+        assert!(error_key(&source) == "camera.tone_mapper");
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        assert!(error_key("[camera]\nwidth = 400\nvfov = = 3") == "line 3");
+    }
+
+    #[test]
+    fn test_background() {
+        let scene = parse(SCENE).unwrap();
+        let ray = crate::ray::Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+
+        assert!(scene.camera.background.color(&ray).b == 0.5);
+
+        let scene = parse("[background]\ntype = \"black\"").unwrap();
+
+        assert!(scene.camera.background.color(&ray).b == 0.0);
+
+        let source = SCENE.replace("color = [0.0, 0.0, 0.5]", "colour = [0.0, 0.0, 0.5]");
+
+        assert!(error_key(&source) == "background.colour");
+    }
+
+    #[test]
+    fn test_negative_emission() {
+        let source = SCENE.replace("emit = [4.0, 4.0, 4.0]", "emit = [4.0, -4.0, 4.0]");
+
+        assert!(error_key(&source) == "materials.lamp.emit");
+    }
+
+    #[test]
+    fn test_invalid_value() {
+        let source = SCENE.replace("radius = 1.0", "radius = -1.0");
+
+        assert!(error_key(&source) == "shapes[0].radius");
+        assert!(error_key("[camera]\nwidth = 0") == "camera.width");
+        assert!(error_key("[camera]\nmax_depth = 0") == "camera.max_depth");
+    }
+
+    #[test]
+    fn test_planar_shapes() {
+        let source = r#"
+[materials.red]
+type = "lambertian"
+albedo = [0.8, 0.1, 0.1]
+
+[[shapes]]
+type = "quad"
+corner = [0.0, 0.0, 0.0]
+u = [1.0, 0.0, 0.0]
+v = [0.0, 1.0, 0.0]
+material = "red"
+
+[[shapes]]
+type = "disk"
+center = [0.0, 0.0, 0.0]
+normal = [0.0, 1.0, 0.0]
+radius = 1.0
+material = "red"
+
+[[shapes]]
+type = "plane"
+point = [0.0, 0.0, 0.0]
+normal = [0.0, 1.0, 0.0]
+material = "red"
+
+[[shapes]]
+type = "box"
+a = [0.0, 0.0, 0.0]
+b = [1.0, 1.0, 1.0]
+material = "red"
+"#;
+
+        assert!(parse(source).unwrap().world.len() == 4);
+        assert!(
+            error_key(&source.replace("v = [0.0, 1.0, 0.0]", "v = [2.0, 0.0, 0.0]"))
+                == "shapes[0].v"
+        );
+        assert!(
+            error_key(&source.replacen("normal = [0.0, 1.0, 0.0]", "normal = [0.0, 0.0, 0.0]", 1))
+                == "shapes[1].normal"
+        );
+    }
+
+    #[test]
+    fn test_round_shapes() {
+        let source = r#"
+[materials.red]
+type = "lambertian"
+albedo = [0.8, 0.1, 0.1]
+
+[[shapes]]
+type = "cylinder"
+base = [0.0, 0.0, 0.0]
+top = [0.0, 1.0, 0.0]
+radius = 0.5
+material = "red"
+
+[[shapes]]
+type = "cone"
+base = [2.0, 0.0, 0.0]
+base_radius = 0.5
+top = [2.0, 1.0, 0.0]
+capped = false
+material = "red"
+
+[[shapes]]
+type = "capsule"
+a = [4.0, 0.0, 0.0]
+b = [4.0, 1.0, 0.0]
+radius = 0.25
+material = "red"
+
+[[shapes]]
+type = "torus"
+center = [6.0, 0.0, 0.0]
+major_radius = 1.0
+minor_radius = 0.25
+material = "red"
+"#;
+
+        assert!(parse(source).unwrap().world.len() == 4);
+        assert!(
+            error_key(&source.replace("top = [0.0, 1.0, 0.0]", "top = [0.0, 0.0, 0.0]"))
+                == "shapes[0].top"
+        );
+        assert!(
+            error_key(&source.replace("base_radius = 0.5", "base_radius = -0.5"))
+                == "shapes[1].base_radius"
+        );
+        assert!(
+            error_key(&source.replace("minor_radius = 0.25", "minor_radius = 0.0"))
+                == "shapes[3].minor_radius"
+        );
+        assert!(
+            error_key(&source.replace("major_radius", "axis = [0.0, 0.0, 0.0]\nmajor_radius"))
+                == "shapes[3].axis"
+        );
+    }
+
+    #[test]
+    fn test_transforms() {
+        let source = r#"
+[materials.red]
+type = "lambertian"
+albedo = [0.8, 0.1, 0.1]
+
+[[shapes]]
+type = "box"
+a = [-0.5, -0.5, -0.5]
+b = [0.5, 0.5, 0.5]
+material = "red"
+name = "cube"
+transform = [{ scale = [2.0, 1.0, 1.0] }, { rotate = { axis = [0.0, 1.0, 0.0], angle = 45.0 } }]
+
+[[shapes]]
+type = "instance"
+of = "cube"
+transform = [{ translate = [0.0, 3.0, 0.0] }]
+"#;
+        let scene = parse(source).unwrap();
+        let bbox = scene.world[1].bounding_box();
+
+        assert!(scene.world.len() == 2);
+        assert!(
+            (bbox.min.j - 2.5).abs() < 1e-12 && (bbox.max.i - 1.5 / 2.0_f64.sqrt()).abs() < 1e-12
+        );
+        assert!(error_key(&source.replace("of = \"cube\"", "of = \"sphere\"")) == "shapes[1].of");
+        assert!(
+            error_key(&source.replace("[2.0, 1.0, 1.0]", "[2.0, 0.0, 1.0]"))
+                == "shapes[0].transform[0].scale"
+        );
+        assert!(
+            error_key(&source.replace("[0.0, 1.0, 0.0]", "[0.0, 0.0, 0.0]"))
+                == "shapes[0].transform[1].rotate.axis"
+        );
+        assert!(error_key(&source.replace("translate", "shear")) == "shapes[1].transform[0]");
+        assert!(
+            error_key(&source.replace(
+                "type = \"instance\"",
+                "name = \"cube\"\ntype = \"instance\""
+            )) == "shapes[1].name"
+        );
+    }
+
+    #[test]
+    fn test_motion() {
+        let source = r#"
+[camera]
+shutter_open = 0.0
+shutter_close = 0.5
+
+[materials.red]
+type = "lambertian"
+albedo = [0.8, 0.1, 0.1]
+
+[[shapes]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+center_end = [0.0, 2.0, 0.0]
+radius = 1.0
+material = "red"
+
+[[shapes]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "red"
+transform_end = [{ translate = [4.0, 0.0, 0.0] }]
+"#;
+        let scene = parse(source).unwrap();
+
+        assert!(scene.camera.shutter_close == 0.5);
+        assert!(scene.world[0].bounding_box().max.j == 3.0);
+        assert!(scene.world[1].bounding_box().max.i >= 5.0);
+        assert!(
+            error_key(&source.replace("shutter_close = 0.5", "shutter_close = -0.5"))
+                == "camera.shutter_close"
+        );
+    }
+
+    #[test]
+    fn test_medium() {
+        let source = r#"
+[materials.smoke]
+type = "isotropic"
+albedo = [0.9, 0.9, 0.9]
+
+[[shapes]]
+type = "box"
+a = [0.0, 0.0, 0.0]
+b = [1.0, 1.0, 1.0]
+material = "smoke"
+density = 0.5
+name = "fog"
+
+[[shapes]]
+type = "instance"
+of = "fog"
+"#;
+
+        assert!(parse(source).unwrap().world.len() == 2);
+        assert!(
+            error_key(&source.replace("density = 0.5", "density = 0.0")) == "shapes[0].density"
+        );
+        assert!(
+            error_key(&source.replace("of = \"fog\"", "of = \"fog\"\ndensity = 1.0"))
+                == "shapes[1].density"
+        );
+    }
+
+    #[test]
+    fn test_lights() {
+        let source = r#"
+[materials.lamp]
+type = "diffuse_light"
+emit = [4.0, 4.0, 4.0]
+
+[materials.red]
+type = "lambertian"
+albedo = [0.8, 0.1, 0.1]
+
+[[shapes]]
+type = "quad"
+corner = [0.0, 2.0, 0.0]
+u = [1.0, 0.0, 0.0]
+v = [0.0, 0.0, 1.0]
+material = "lamp"
+name = "panel"
+
+[[shapes]]
+type = "instance"
+of = "panel"
+transform = [{ translate = [2.0, 0.0, 0.0] }]
+
+[[shapes]]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "red"
+"#;
+        let scene = parse(source).unwrap();
+
+        assert!(scene.world.len() == 3);
+        assert!(scene.lights.len() == 2);
+    }
+
+    #[test]
+    fn test_integrator() {
+        assert!(parse("[integrator]\ntype = \"path\"\nlight_sampling = \"mixture\"").is_ok());
+        assert!(parse("[integrator]\ntype = \"path\"\nlight_sampling = \"none\"").is_ok());
+        assert!(parse("[integrator]\ntype = \"ambient_occlusion\"\ndistance = 2.0").is_ok());
+        assert!(parse("[integrator]\ntype = \"normals\"").is_ok());
+
+        let source = "[integrator]\ntype = \"depth\"\nmax_distance = -1.0";
+        assert!(error_key(source) == "integrator.max_distance");
+        let source = "[integrator]\ntype = \"path\"\nlight_sampling = \"heuristic\"";
+        assert!(error_key(source) == "integrator.light_sampling");
+        assert!(error_key("[integrator]\ntype = \"photon\"") == "integrator.type");
+    }
+
+    #[test]
+    fn test_conductor() {
+        let gold = "[materials.gold]\ntype = \"conductor\"\nmetal = \"gold\"\nroughness = 0.2";
+        let custom =
+            "[materials.custom]\ntype = \"conductor\"\neta = [0.2, 0.9, 1.1]\nk = [3.9, 2.5, 2.1]";
+        assert!(parse(gold).is_ok());
+        assert!(parse(custom).is_ok());
+
+        assert!(error_key(&gold.replace("0.2", "1.5")) == "materials.gold.roughness");
+        assert!(error_key(&gold.replace("\"gold\"", "\"brass\"")) == "materials.gold.metal");
+        assert!(error_key(&format!("{}\nk = [3.9, 2.5, 2.1]", gold)) == "materials.gold.metal");
+        assert!(error_key(&custom.replace("k = ", "kk = ")) == "materials.custom.kk");
+        let source = custom.replace("\nk = [3.9, 2.5, 2.1]", "");
+        assert!(error_key(&source) == "materials.custom.eta");
+    }
+
+    #[test]
+    fn test_rough_dielectric() {
+        let source = SCENE.replace("ir = 1.5", "ir = 1.5\nroughness = 0.4");
+        assert!(parse(&source).is_ok());
+
+        let source = SCENE.replace("ir = 1.5", "ir = 1.5\nroughness = -0.1");
+        assert!(error_key(&source) == "materials.glass.roughness");
+    }
+
+    #[test]
+    fn test_missing_obj() {
+        let source = "[[shapes]]\ntype = \"obj\"\npath = \"missing.obj\"";
+
+        assert!(error_key(source) == "shapes[0].path");
+    }
+
+    #[test]
+    fn test_textures() {
+        assert!(parse(SCENE).is_ok());
+
+        let source = SCENE.replace("albedo = \"tiles\"", "albedo = \"marble\"");
+
+        assert!(error_key(&source) == "materials.floor.albedo");
+
+        let source = SCENE.replace("scale = 0.5", "scale = 0.0");
+
+        assert!(error_key(&source) == "textures.tiles.scale");
+
+        let source = "[textures.photo]\ntype = \"image\"\npath = \"missing.png\"";
+
+        assert!(error_key(source) == "textures.photo.path");
+    }
+
+    #[test]
+    fn test_environment() {
+        let source = "[background]\ntype = \"environment\"\npath = \"missing.hdr\"";
+
+        assert!(error_key(source) == "background.path");
+        assert!(error_key(&format!("{}\nintensity = -1.0", source)) == "background.intensity");
+        assert!(error_key(&format!("{}\nintensity = nan", source)) == "background.intensity");
+    }
+
+    #[test]
+    fn test_procedural_textures() {
+        let source = "[textures.stone]\ntype = \"marble\"\nfrequency = 2.0\noctaves = 5";
+
+        assert!(parse(source).is_ok());
+        assert!(parse("[textures.grid]\ntype = \"uv_grid\"").is_ok());
+        assert!(
+            error_key(&source.replace("octaves = 5", "octaves = 0")) == "textures.stone.octaves"
+        );
+        assert!(
+            error_key("[textures.smoke]\ntype = \"noise\"\nfrequency = -1.0")
+                == "textures.smoke.frequency"
+        );
+    }
+
+    #[test]
+    fn test_random_spheres_is_reproducible() {
+        // the corners of every object's box, in order
+        let layout = |seed| -> Vec<[f64; 6]> {
+            random_spheres(seed)
+                .world
+                .iter()
+                .map(|object| {
+                    let Aabb { min, max } = object.bounding_box();
+                    [min.i, min.j, min.k, max.i, max.j, max.k]
+                })
+                .collect()
+        };
+
+        assert!(layout(1) == layout(1));
+        assert!(layout(1).len() > 4);
+        assert!(layout(1) != layout(2));
+    }
+}
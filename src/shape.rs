@@ -1,20 +1,70 @@
 use crate::{
+    aabb::Aabb,
     hittable::{HitRecord, Hittable},
     material::Material,
+    pdf::solid_angle_pdf,
+    random::RandomGenerator,
     ray::Ray,
     vec3::{Point, Vec3},
 };
-use std::rc::Rc;
+use rand::Rng;
+use std::{f64::consts::PI, sync::Arc};
 
+mod planar;
+mod polynomial;
+mod revolution;
+mod triangle;
+
+pub use planar::{Cuboid, Disk, Plane, Quad};
+pub use revolution::{Capsule, Cone, Cylinder, Torus};
+pub use triangle::{MeshFace, Triangle, TriangleMesh};
+
+/// Sphere around `center`. Its uvs are longitude and latitude: `u` goes around the j axis
+/// starting from -i, `v` from the bottom pole to the top one.
 pub struct Sphere {
-    pub center: Point,
+    pub center: Point, // at time 0
+    pub motion: Vec3,  // how far the center goes from time 0 to time 1
     pub radius: f64,
-    pub material: Rc<dyn Material>,
+    pub material: Arc<dyn Material>,
+}
+
+impl Sphere {
+    pub fn new(center: Point, radius: f64, material: Arc<dyn Material>) -> Sphere {
+        Sphere {
+            center,
+            motion: Vec3::new(0.0, 0.0, 0.0),
+            radius,
+            material,
+        }
+    }
+
+    // moves in a straight line from `start` at time 0 to `end` at time 1, and holds still
+    // outside of that
+    pub fn moving(start: Point, end: Point, radius: f64, material: Arc<dyn Material>) -> Sphere {
+        Sphere {
+            center: start,
+            motion: end - start,
+            radius,
+            material,
+        }
+    }
+
+    pub fn center_at(&self, time: f64) -> Point {
+        self.center + time.clamp(0.0, 1.0) * self.motion
+    }
+
+    // (u, v) of a point on the unit sphere around the origin
+    fn uv(p: Vec3) -> (f64, f64) {
+        let theta = (-p.j).clamp(-1.0, 1.0).acos();
+        let phi = (-p.k).atan2(p.i) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
-        let ac = ray.origin() - self.center;
+        let center = self.center_at(ray.time());
+        let ac = ray.origin() - center;
         let a = ray.dir().dot(ray.dir());
         let b = 2.0 * ac.dot(ray.dir());
         let c = ac.dot(ac) - self.radius * self.radius;
@@ -33,12 +83,15 @@ impl Hittable for Sphere {
             }
         }
         let intersection = ray.at(root);
-        let outside_normal = (intersection - self.center) / self.radius;
+        let outside_normal = (intersection - center) / self.radius;
+        let (u, v) = Sphere::uv(outside_normal);
         let mut tmp = HitRecord {
             intersection,
             t: root,
             normal: Vec3::new(0.0, 0.0, 0.0),
             out_facing: false,
+            u,
+            v,
             material: self.material.clone(),
         };
 
@@ -46,4 +99,69 @@ impl Hittable for Sphere {
 
         Some(HitRecord { ..tmp })
     }
+
+    fn bounding_box(&self) -> Aabb {
+        let radius = Vec3::new(self.radius, self.radius, self.radius);
+        let end = self.center + self.motion;
+        Aabb::new(self.center - radius, self.center + radius)
+            .surrounding(&Aabb::new(end - radius, end + radius))
+    }
+
+    // from outside, uniform over the cone of directions the sphere covers, from inside
+    // uniform over its surface
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        let center = self.center_at(ray.time());
+        let distance_squared = (center - ray.origin()).length_pow2();
+        if distance_squared <= self.radius * self.radius {
+            let area = 4.0 * PI * self.radius * self.radius;
+            return solid_angle_pdf(self, ray, area);
+        }
+        if self.hit(ray, 0.0, f64::INFINITY).is_none() {
+            return 0.0;
+        }
+
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+
+    fn sample(&self, origin: Point, time: f64, random_generator: &mut RandomGenerator) -> Vec3 {
+        let center = self.center_at(time);
+        let to_center = center - origin;
+        let distance_squared = to_center.length_pow2();
+        if distance_squared <= self.radius * self.radius {
+            return center + self.radius * Vec3::random_unit_vector(random_generator) - origin;
+        }
+
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let r1: f64 = random_generator.gen_range(0.0..1.0);
+        let r2: f64 = random_generator.gen_range(0.0..1.0);
+        let cos_theta = 1.0 + r2 * (cos_theta_max - 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * r1;
+        let axis = to_center.unit_vector();
+        let (tangent, bitangent) = axis.tangent_frame();
+        sin_theta * phi.cos() * tangent + sin_theta * phi.sin() * bitangent + cos_theta * axis
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Lambertian, Rgb};
+
+    #[test]
+    fn test_sphere_uv() {
+        let sphere = Sphere::new(
+            Point::new(0.0, 0.0, -3.0),
+            2.0,
+            Arc::new(Lambertian::new(Rgb::white())),
+        );
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = sphere.hit(&ray, 0.0, f64::INFINITY).unwrap();
+
+        // the +k side, a quarter turn from -i
+        assert!((hit_record.u - 0.25).abs() < 1e-12 && (hit_record.v - 0.5).abs() < 1e-12);
+        assert!(Sphere::uv(Vec3::new(1.0, 0.0, 0.0)) == (0.5, 0.5));
+        assert!(Sphere::uv(Vec3::new(0.0, 1.0, 0.0)).1 == 1.0);
+    }
 }
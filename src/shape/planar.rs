@@ -0,0 +1,381 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use rand::Rng;
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    pdf::solid_angle_pdf,
+    random::RandomGenerator,
+    ray::Ray,
+    vec3::{Point, Vec3},
+};
+
+// flat shapes get a box this thick so the BVH slab test doesn't miss them
+const BBOX_PADDING: f64 = 1e-4;
+
+// rays closer than this to parallel never hit a plane
+const PARALLEL_EPSILON: f64 = 1e-8;
+
+// distance along `ray` to the plane through `point` with unit `normal`, within the range
+fn plane_t(point: Point, normal: Vec3, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<f64> {
+    let denom = normal.dot(ray.dir());
+    if denom.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+
+    let t = normal.dot(point - ray.origin()) / denom;
+    if t <= ray_tmin || t >= ray_tmax {
+        return None;
+    }
+    Some(t)
+}
+
+fn hit_record(
+    ray: &Ray,
+    t: f64,
+    outside_normal: Vec3,
+    (u, v): (f64, f64),
+    material: &Arc<dyn Material>,
+) -> HitRecord {
+    let mut hit_record = HitRecord {
+        intersection: ray.at(t),
+        t,
+        normal: Vec3::new(0.0, 0.0, 0.0),
+        out_facing: false,
+        u,
+        v,
+        material: material.clone(),
+    };
+    hit_record.set_outside_normal(ray, outside_normal);
+    hit_record
+}
+
+/// Parallelogram with a corner at `corner` and sides `edge_u` and `edge_v`. The uvs run from
+/// 0 to 1 along the two edges and the outside is the side `edge_u × edge_v` points to.
+pub struct Quad {
+    corner: Point,
+    edge_u: Vec3,
+    edge_v: Vec3,
+    normal: Vec3,
+    w: Vec3, // n / (n · n), maps a point of the plane to its (u, v)
+    material: Arc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(corner: Point, edge_u: Vec3, edge_v: Vec3, material: Arc<dyn Material>) -> Quad {
+        let n = edge_u.cross(edge_v);
+        Quad {
+            corner,
+            edge_u,
+            edge_v,
+            normal: n.unit_vector(),
+            w: n / n.dot(n),
+            material,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        let t = plane_t(self.corner, self.normal, ray, ray_tmin, ray_tmax)?;
+        let planar = ray.at(t) - self.corner;
+        let alpha = self.w.dot(planar.cross(self.edge_v));
+        let beta = self.w.dot(self.edge_u.cross(planar));
+
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        Some(hit_record(
+            ray,
+            t,
+            self.normal,
+            (alpha, beta),
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let diagonal1 = Aabb::new(self.corner, self.corner + self.edge_u + self.edge_v);
+        let diagonal2 = Aabb::new(self.corner + self.edge_u, self.corner + self.edge_v);
+        diagonal1.surrounding(&diagonal2).padded(BBOX_PADDING)
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        solid_angle_pdf(self, ray, self.edge_u.cross(self.edge_v).length())
+    }
+
+    fn sample(&self, origin: Point, _: f64, random_generator: &mut RandomGenerator) -> Vec3 {
+        let point = self.corner
+            + random_generator.gen_range(0.0..1.0) * self.edge_u
+            + random_generator.gen_range(0.0..1.0) * self.edge_v;
+        point - origin
+    }
+}
+
+/// Flat disk facing `normal`. The uvs are those of the square the disk is inscribed in, so an
+/// image texture is mapped on it like a decal.
+pub struct Disk {
+    center: Point,
+    radius: f64,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Point, normal: Vec3, radius: f64, material: Arc<dyn Material>) -> Disk {
+        let normal = normal.unit_vector();
+        let (tangent, bitangent) = normal.tangent_frame();
+        Disk {
+            center,
+            radius,
+            normal,
+            tangent,
+            bitangent,
+            material,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        let t = plane_t(self.center, self.normal, ray, ray_tmin, ray_tmax)?;
+        let planar = ray.at(t) - self.center;
+
+        if planar.length_pow2() > self.radius * self.radius {
+            return None;
+        }
+        let u = 0.5 + planar.dot(self.tangent) / (2.0 * self.radius);
+        let v = 0.5 + planar.dot(self.bitangent) / (2.0 * self.radius);
+        Some(hit_record(ray, t, self.normal, (u, v), &self.material))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // a circle reaches radius * sin(angle between the axis and the normal) along each axis
+        let n = self.normal;
+        let extent = self.radius
+            * Vec3::new(
+                (1.0 - n.i * n.i).max(0.0).sqrt(),
+                (1.0 - n.j * n.j).max(0.0).sqrt(),
+                (1.0 - n.k * n.k).max(0.0).sqrt(),
+            );
+        Aabb::new(self.center - extent, self.center + extent).padded(BBOX_PADDING)
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        solid_angle_pdf(self, ray, PI * self.radius * self.radius)
+    }
+
+    fn sample(&self, origin: Point, _: f64, random_generator: &mut RandomGenerator) -> Vec3 {
+        // the square root spreads the points evenly instead of bunching them at the center
+        let r = self.radius * random_generator.gen_range(0.0..1.0_f64).sqrt();
+        let phi = 2.0 * PI * random_generator.gen_range(0.0..1.0);
+        self.center + r * phi.cos() * self.tangent + r * phi.sin() * self.bitangent - origin
+    }
+}
+
+/// Infinite plane through `point` facing `normal`. The uvs are plain distances along two
+/// directions of the plane, a checker or a uv grid repeats over it without end.
+pub struct Plane {
+    point: Point,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vec3, material: Arc<dyn Material>) -> Plane {
+        let normal = normal.unit_vector();
+        let (tangent, bitangent) = normal.tangent_frame();
+        Plane {
+            point,
+            normal,
+            tangent,
+            bitangent,
+            material,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        let t = plane_t(self.point, self.normal, ray, ray_tmin, ray_tmax)?;
+        let planar = ray.at(t) - self.point;
+        let uv = (planar.dot(self.tangent), planar.dot(self.bitangent));
+        Some(hit_record(ray, t, self.normal, uv, &self.material))
+    }
+
+    // the BVH can't split it away from anything, every ray visits its branch
+    fn bounding_box(&self) -> Aabb {
+        Aabb::infinite()
+    }
+}
+
+/// Axis-aligned box between two opposite corners, made of six quads facing out. Each face
+/// has its own uvs from 0 to 1.
+pub struct Cuboid {
+    faces: [Quad; 6],
+    bbox: Aabb,
+}
+
+impl Cuboid {
+    pub fn new(a: Point, b: Point, material: Arc<dyn Material>) -> Cuboid {
+        let bbox = Aabb::new(a, b);
+        let (min, max) = (bbox.min, bbox.max);
+        let dx = Vec3::new(max.i - min.i, 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.j - min.j, 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.k - min.k);
+
+        let faces = [
+            Quad::new(Point::new(min.i, min.j, max.k), dx, dy, material.clone()), // front
+            Quad::new(Point::new(max.i, min.j, max.k), -dz, dy, material.clone()), // right
+            Quad::new(Point::new(max.i, min.j, min.k), -dx, dy, material.clone()), // back
+            Quad::new(Point::new(min.i, min.j, min.k), dz, dy, material.clone()), // left
+            Quad::new(Point::new(min.i, max.j, max.k), dx, -dz, material.clone()), // top
+            Quad::new(Point::new(min.i, min.j, min.k), dx, dz, material),         // bottom
+        ];
+        Cuboid { faces, bbox }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        let mut closest: Option<HitRecord> = None;
+        for face in &self.faces {
+            let ray_tmax = closest.as_ref().map_or(ray_tmax, |rec| rec.t);
+            if let Some(hit_record) = face.hit(ray, ray_tmin, ray_tmax) {
+                closest = Some(hit_record);
+            }
+        }
+        closest
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.padded(BBOX_PADDING)
+    }
+
+    // a face picked at random then a point on it, like a list of the six quads
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        self.faces
+            .iter()
+            .map(|face| face.pdf_value(ray))
+            .sum::<f64>()
+            / 6.0
+    }
+
+    fn sample(&self, origin: Point, time: f64, random_generator: &mut RandomGenerator) -> Vec3 {
+        self.faces[random_generator.gen_range(0..6)].sample(origin, time, random_generator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Lambertian, Rgb};
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Rgb::white()))
+    }
+
+    fn ray(origin: Point, direction: Vec3) -> Ray {
+        Ray::new(origin, direction)
+    }
+
+    #[test]
+    fn test_quad() {
+        let quad = Quad::new(
+            Point::new(-1.0, -1.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 4.0, 0.0),
+            material(),
+        );
+        let front = ray(Point::new(0.5, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        let back = ray(Point::new(0.5, 0.0, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        let beside = ray(Point::new(1.5, 0.0, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        let parallel = ray(Point::new(0.0, 0.0, 3.0), Vec3::new(1.0, 0.0, 0.0));
+
+        let hit_record = quad.hit(&front, 0.0, f64::INFINITY).unwrap();
+        assert!(hit_record.t == 3.0 && hit_record.out_facing);
+        assert!(hit_record.normal.k == 1.0);
+        assert!(hit_record.u == 0.75 && hit_record.v == 0.25);
+
+        let hit_record = quad.hit(&back, 0.0, f64::INFINITY).unwrap();
+        assert!(!hit_record.out_facing && hit_record.normal.k == -1.0);
+
+        assert!(quad.hit(&beside, 0.0, f64::INFINITY).is_none());
+        assert!(quad.hit(&parallel, 0.0, f64::INFINITY).is_none());
+        assert!(quad.hit(&front, 0.0, 2.0).is_none());
+    }
+
+    #[test]
+    fn test_disk() {
+        let disk = Disk::new(
+            Point::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            1.0,
+            material(),
+        );
+        let inside = ray(Point::new(0.5, 3.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+        let outside = ray(Point::new(0.8, 3.0, 0.8), Vec3::new(0.0, -1.0, 0.0));
+
+        let hit_record = disk.hit(&inside, 0.0, f64::INFINITY).unwrap();
+        assert!(hit_record.t == 2.0 && hit_record.out_facing && hit_record.normal.j == 1.0);
+        assert!((0.0..=1.0).contains(&hit_record.u) && (0.0..=1.0).contains(&hit_record.v));
+        assert!(disk.hit(&outside, 0.0, f64::INFINITY).is_none());
+
+        let bbox = disk.bounding_box();
+        assert!(bbox.max.i == 1.0 && bbox.max.j < 1.001 && bbox.min.k == -1.0);
+    }
+
+    #[test]
+    fn test_plane() {
+        let plane = Plane::new(
+            Point::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            material(),
+        );
+        let far = ray(Point::new(1e6, 1.0, -1e6), Vec3::new(0.0, -1.0, 0.0));
+        let below = ray(Point::new(0.0, -2.0, 0.0), Vec3::new(0.3, 1.0, 0.0));
+
+        let hit_record = plane.hit(&far, 0.0, f64::INFINITY).unwrap();
+        assert!(hit_record.t == 2.0 && hit_record.out_facing);
+
+        let hit_record = plane.hit(&below, 0.0, f64::INFINITY).unwrap();
+        assert!(!hit_record.out_facing && hit_record.normal.j == -1.0);
+        assert!(plane.bounding_box().hit(&far, 0.0, f64::INFINITY));
+    }
+
+    #[test]
+    fn test_cuboid() {
+        let cuboid = Cuboid::new(
+            Point::new(1.0, 1.0, 1.0),
+            Point::new(-1.0, -1.0, -1.0),
+            material(),
+        );
+        let rays = [
+            (Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, 1.0)),
+            (Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, -1.0)),
+            (Vec3::new(5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)),
+            (Vec3::new(-5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0)),
+            (Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+            (Vec3::new(0.0, -5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)),
+        ];
+
+        // every face is hit from outside first, with its normal facing out
+        for (origin, outward) in rays {
+            let hit_record = cuboid
+                .hit(&ray(origin, -outward), 0.0, f64::INFINITY)
+                .unwrap();
+            assert!(hit_record.t == 4.0 && hit_record.out_facing);
+            assert!((hit_record.normal - outward).length() < 1e-12);
+        }
+
+        let inside = ray(Point::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit_record = cuboid.hit(&inside, 0.0, f64::INFINITY).unwrap();
+        assert!(hit_record.t == 1.0 && !hit_record.out_facing);
+    }
+}
@@ -0,0 +1,172 @@
+// Real roots of low degree polynomials, for the shapes whose intersection can't be written as
+// a plain quadratic. Coefficients go from the highest degree down.
+
+const EPSILON: f64 = 1e-12;
+
+// a few Newton steps on c[0] x^n + ... + c[n], the closed forms lose digits near double roots
+fn polish(c: &[f64], mut x: f64) -> f64 {
+    for _ in 0..4 {
+        let (mut value, mut derivative) = (0.0, 0.0);
+        for &coefficient in c {
+            derivative = derivative * x + value;
+            value = value * x + coefficient;
+        }
+        if derivative == 0.0 {
+            break;
+        }
+        let step = value / derivative;
+        x -= step;
+        if step.abs() <= EPSILON * x.abs() {
+            break;
+        }
+    }
+    x
+}
+
+pub(super) fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a.abs() < EPSILON {
+        return if b.abs() < EPSILON {
+            vec![]
+        } else {
+            vec![-c / b]
+        };
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return vec![];
+    }
+    // avoids the cancellation of -b + sqrt(d) when b is large
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        return vec![0.0];
+    }
+    let (x0, x1) = (q / a, c / q);
+    vec![x0.min(x1), x0.max(x1)]
+}
+
+pub(super) fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a.abs() < EPSILON {
+        return solve_quadratic(b, c, d);
+    }
+
+    // x^3 + p x^2 + q x + r, with the trigonometric or Cardano form
+    let (p, q, r) = (b / a, c / a, d / a);
+    let big_q = (p * p - 3.0 * q) / 9.0;
+    let big_r = (2.0 * p * p * p - 9.0 * p * q + 27.0 * r) / 54.0;
+    let q3 = big_q * big_q * big_q;
+
+    let roots = if big_r * big_r < q3 {
+        let theta = (big_r / q3.sqrt()).clamp(-1.0, 1.0).acos();
+        let scale = -2.0 * big_q.sqrt();
+        vec![
+            scale * (theta / 3.0).cos() - p / 3.0,
+            scale * ((theta + 2.0 * std::f64::consts::PI) / 3.0).cos() - p / 3.0,
+            scale * ((theta - 2.0 * std::f64::consts::PI) / 3.0).cos() - p / 3.0,
+        ]
+    } else {
+        let s = -big_r.signum() * (big_r.abs() + (big_r * big_r - q3).sqrt()).cbrt();
+        let t = if s == 0.0 { 0.0 } else { big_q / s };
+        vec![s + t - p / 3.0]
+    };
+
+    let mut roots: Vec<f64> = roots
+        .into_iter()
+        .map(|x| polish(&[1.0, p, q, r], x))
+        .collect();
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+/// Real roots of `a x^4 + b x^3 + c x^2 + d x + e` in increasing order, with Ferrari's
+/// method: the depressed quartic is split in two quadratics with a root of its resolvent
+/// cubic, then every root is polished on the original polynomial.
+pub(super) fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if a.abs() < EPSILON {
+        return solve_cubic(b, c, d, e);
+    }
+
+    // x = y - b / 4 gives y^4 + p y^2 + q y + r
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+    let shift = -b / 4.0;
+    let b2 = b * b;
+    let p = c - 3.0 * b2 / 8.0;
+    let q = d - b * c / 2.0 + b2 * b / 8.0;
+    let r = e - b * d / 4.0 + b2 * c / 16.0 - 3.0 * b2 * b2 / 256.0;
+
+    let mut ys = Vec::new();
+    if q.abs() < EPSILON {
+        // biquadratic
+        for z in solve_quadratic(1.0, p, r) {
+            if z >= 0.0 {
+                ys.push(z.sqrt());
+                ys.push(-z.sqrt());
+            }
+        }
+    } else {
+        // the resolvent always has a positive root when q != 0
+        let m = solve_cubic(8.0, 8.0 * p, 2.0 * p * p - 8.0 * r, -q * q)
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if m <= 0.0 {
+            return vec![];
+        }
+        let s = (2.0 * m).sqrt();
+        ys.extend(solve_quadratic(1.0, -s, p / 2.0 + m + q / (2.0 * s)));
+        ys.extend(solve_quadratic(1.0, s, p / 2.0 + m - q / (2.0 * s)));
+    }
+
+    let mut roots: Vec<f64> = ys
+        .into_iter()
+        .map(|y| polish(&[1.0, b, c, d, e], y + shift))
+        .collect();
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: &[f64], expected: &[f64]) -> bool {
+        actual.len() == expected.len()
+            && actual
+                .iter()
+                .zip(expected)
+                .all(|(a, e)| (a - e).abs() < 1e-9)
+    }
+
+    #[test]
+    fn test_quadratic() {
+        assert!(close(&solve_quadratic(1.0, -3.0, 2.0), &[1.0, 2.0]));
+        assert!(close(&solve_quadratic(0.0, 2.0, -4.0), &[2.0]));
+        assert!(solve_quadratic(1.0, 0.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn test_cubic() {
+        // (x - 1)(x + 2)(x - 3)
+        assert!(close(&solve_cubic(1.0, -2.0, -5.0, 6.0), &[-2.0, 1.0, 3.0]));
+        // (x - 2)(x^2 + 1)
+        assert!(close(&solve_cubic(2.0, -4.0, 2.0, -4.0), &[2.0]));
+    }
+
+    #[test]
+    fn test_quartic() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert!(close(
+            &solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0),
+            &[1.0, 2.0, 3.0, 4.0]
+        ));
+        // (x^2 - 4)(x^2 + 1), biquadratic
+        assert!(close(
+            &solve_quartic(1.0, 0.0, -3.0, 0.0, -4.0),
+            &[-2.0, 2.0]
+        ));
+        // (x^2 + 1)(x^2 + 2) has no real roots
+        assert!(solve_quartic(1.0, 0.0, 3.0, 0.0, 2.0).is_empty());
+        // (x - 0.5)(x - 100)(x^2 + 1), far apart roots
+        let roots = solve_quartic(1.0, -100.5, 51.0, -100.5, 50.0);
+        assert!(close(&roots, &[0.5, 100.0]));
+    }
+}
@@ -0,0 +1,610 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use super::polynomial::{solve_quadratic, solve_quartic};
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+    vec3::{Point, Vec3},
+};
+
+// rays closer than this to parallel miss the caps
+const PARALLEL_EPSILON: f64 = 1e-8;
+
+/// Orthonormal frame whose local j axis is the axis of revolution of a shape. The shapes
+/// intersect rays in local space, where their equations are simple, and only rotate the
+/// normal back, distances along the ray are the same in both.
+struct Frame {
+    origin: Point,
+    tangent: Vec3,
+    axis: Vec3,
+    bitangent: Vec3,
+}
+
+impl Frame {
+    fn new(origin: Point, axis: Vec3) -> Frame {
+        let axis = axis.unit_vector();
+        let (tangent, bitangent) = axis.tangent_frame();
+        Frame {
+            origin,
+            tangent,
+            axis,
+            bitangent,
+        }
+    }
+
+    fn to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(v.dot(self.tangent), v.dot(self.axis), v.dot(self.bitangent))
+    }
+
+    fn ray_to_local(&self, ray: &Ray) -> Ray {
+        Ray::with_time(
+            self.to_local(ray.origin() - self.origin),
+            self.to_local(ray.dir()),
+            ray.time(),
+        )
+    }
+
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        v.i * self.tangent + v.j * self.axis + v.k * self.bitangent
+    }
+
+    // box of a circle of `radius` around the axis at local height `height`
+    fn circle_box(&self, height: f64, radius: f64) -> Aabb {
+        let center = self.origin + height * self.axis;
+        let a = self.axis;
+        let extent = radius
+            * Vec3::new(
+                (1.0 - a.i * a.i).max(0.0).sqrt(),
+                (1.0 - a.j * a.j).max(0.0).sqrt(),
+                (1.0 - a.k * a.k).max(0.0).sqrt(),
+            );
+        Aabb::new(center - extent, center + extent)
+    }
+}
+
+// a local space hit, the closest one becomes the `HitRecord`
+struct LocalHit {
+    t: f64,
+    normal: Vec3, // unit, pointing outside
+    u: f64,
+    v: f64,
+}
+
+fn keep_closest(closest: &mut Option<LocalHit>, hit: LocalHit) {
+    if closest.as_ref().is_none_or(|c| hit.t < c.t) {
+        *closest = Some(hit);
+    }
+}
+
+fn to_hit_record(
+    frame: &Frame,
+    ray: &Ray,
+    hit: Option<LocalHit>,
+    material: &Arc<dyn Material>,
+) -> Option<HitRecord> {
+    let hit = hit?;
+    let mut hit_record = HitRecord {
+        intersection: ray.at(hit.t),
+        t: hit.t,
+        normal: Vec3::new(0.0, 0.0, 0.0),
+        out_facing: false,
+        u: hit.u,
+        v: hit.v,
+        material: material.clone(),
+    };
+    hit_record.set_outside_normal(ray, frame.to_world(hit.normal).unit_vector());
+    Some(hit_record)
+}
+
+// angle around the local j axis mapped to [0, 1]
+fn azimuth(p: Point) -> f64 {
+    p.k.atan2(p.i) / (2.0 * PI) + 0.5
+}
+
+fn in_range(t: f64, ray_tmin: f64, ray_tmax: f64) -> bool {
+    t > ray_tmin && t < ray_tmax
+}
+
+// the disk of `radius` at local height `height`, facing `up` or down
+fn cap_hit(
+    local: &Ray,
+    height: f64,
+    radius: f64,
+    up: bool,
+    ray_tmin: f64,
+    ray_tmax: f64,
+) -> Option<LocalHit> {
+    if radius <= 0.0 || local.dir().j.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+    let t = (height - local.origin().j) / local.dir().j;
+    let p = local.at(t);
+    if !in_range(t, ray_tmin, ray_tmax) || p.i * p.i + p.k * p.k > radius * radius {
+        return None;
+    }
+    Some(LocalHit {
+        t,
+        normal: Vec3::new(0.0, if up { 1.0 } else { -1.0 }, 0.0),
+        u: 0.5 + p.i / (2.0 * radius),
+        v: 0.5 + p.k / (2.0 * radius),
+    })
+}
+
+/// Cone frustum around the segment from `base` to `top`, with radius `base_radius` at the
+/// base and `top_radius` at the top. A zero top radius gives a pointed cone. The side uvs are
+/// the angle around the axis and the height, the caps are mapped like `Disk`s.
+pub struct Cone {
+    frame: Frame,
+    height: f64,
+    base_radius: f64,
+    top_radius: f64,
+    capped: bool,
+    material: Arc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(
+        base: Point,
+        base_radius: f64,
+        top: Point,
+        top_radius: f64,
+        capped: bool,
+        material: Arc<dyn Material>,
+    ) -> Cone {
+        Cone {
+            frame: Frame::new(base, top - base),
+            height: (top - base).length(),
+            base_radius,
+            top_radius,
+            capped,
+            material,
+        }
+    }
+
+    fn local_hit(&self, local: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<LocalHit> {
+        let (o, d) = (local.origin(), local.dir());
+        // radius(y) = r0 + k y, the side is x^2 + z^2 = radius(y)^2
+        let (r0, k) = (
+            self.base_radius,
+            (self.top_radius - self.base_radius) / self.height,
+        );
+        let radius_o = r0 + k * o.j;
+        let a = d.i * d.i + d.k * d.k - k * k * d.j * d.j;
+        let b = 2.0 * (o.i * d.i + o.k * d.k - k * d.j * radius_o);
+        let c = o.i * o.i + o.k * o.k - radius_o * radius_o;
+
+        let mut closest = None;
+        for t in solve_quadratic(a, b, c) {
+            let p = local.at(t);
+            if !in_range(t, ray_tmin, ray_tmax) || p.j < 0.0 || p.j > self.height {
+                continue;
+            }
+            let normal = Vec3::new(p.i, -k * (r0 + k * p.j), p.k);
+            let normal = if normal.length() > 0.0 {
+                normal.unit_vector()
+            } else {
+                // the tip of a pointed cone
+                Vec3::new(0.0, -k.signum(), 0.0)
+            };
+            keep_closest(
+                &mut closest,
+                LocalHit {
+                    t,
+                    normal,
+                    u: azimuth(p),
+                    v: p.j / self.height,
+                },
+            );
+        }
+
+        if self.capped {
+            let caps = [
+                cap_hit(local, 0.0, self.base_radius, false, ray_tmin, ray_tmax),
+                cap_hit(
+                    local,
+                    self.height,
+                    self.top_radius,
+                    true,
+                    ray_tmin,
+                    ray_tmax,
+                ),
+            ];
+            for hit in caps.into_iter().flatten() {
+                keep_closest(&mut closest, hit);
+            }
+        }
+        closest
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        let local = self.frame.ray_to_local(ray);
+        let hit = self.local_hit(&local, ray_tmin, ray_tmax);
+        to_hit_record(&self.frame, ray, hit, &self.material)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.frame
+            .circle_box(0.0, self.base_radius)
+            .surrounding(&self.frame.circle_box(self.height, self.top_radius))
+    }
+}
+
+/// Finite cylinder around the segment from `base` to `top`, closed by two disks when
+/// `capped`. It's the cone frustum with the same radius at both ends.
+pub struct Cylinder {
+    cone: Cone,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Point,
+        top: Point,
+        radius: f64,
+        capped: bool,
+        material: Arc<dyn Material>,
+    ) -> Cylinder {
+        Cylinder {
+            cone: Cone::new(base, radius, top, radius, capped, material),
+        }
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        self.cone.hit(ray, ray_tmin, ray_tmax)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.cone.bounding_box()
+    }
+}
+
+/// The points within `radius` of the segment from `a` to `b`: a cylinder closed by two
+/// half spheres. `v` runs from the bottom pole to the top one.
+pub struct Capsule {
+    frame: Frame,
+    height: f64,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Capsule {
+    pub fn new(a: Point, b: Point, radius: f64, material: Arc<dyn Material>) -> Capsule {
+        Capsule {
+            frame: Frame::new(a, b - a),
+            height: (b - a).length(),
+            radius,
+            material,
+        }
+    }
+
+    fn local_hit(&self, local: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<LocalHit> {
+        let (o, d) = (local.origin(), local.dir());
+        let r = self.radius;
+        let mut closest = None;
+        let mut push = |t: f64, center_height: f64| {
+            let p = local.at(t);
+            let normal = (p - Vec3::new(0.0, center_height, 0.0)) / r;
+            keep_closest(
+                &mut closest,
+                LocalHit {
+                    t,
+                    normal,
+                    u: azimuth(p),
+                    v: (p.j + r) / (self.height + 2.0 * r),
+                },
+            );
+        };
+
+        // the side, only between the two ends
+        let a = d.i * d.i + d.k * d.k;
+        let b = 2.0 * (o.i * d.i + o.k * d.k);
+        let c = o.i * o.i + o.k * o.k - r * r;
+        for t in solve_quadratic(a, b, c) {
+            let y = o.j + t * d.j;
+            if in_range(t, ray_tmin, ray_tmax) && (0.0..=self.height).contains(&y) {
+                push(t, y);
+            }
+        }
+
+        // the half spheres, only beyond the ends
+        for center_height in [0.0, self.height] {
+            let oc = o - Vec3::new(0.0, center_height, 0.0);
+            for t in solve_quadratic(d.dot(d), 2.0 * oc.dot(d), oc.dot(oc) - r * r) {
+                let y = o.j + t * d.j;
+                let beyond = if center_height == 0.0 {
+                    y < 0.0
+                } else {
+                    y > self.height
+                };
+                if in_range(t, ray_tmin, ray_tmax) && beyond {
+                    push(t, center_height);
+                }
+            }
+        }
+        closest
+    }
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        let local = self.frame.ray_to_local(ray);
+        let hit = self.local_hit(&local, ray_tmin, ray_tmax);
+        to_hit_record(&self.frame, ray, hit, &self.material)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        let a = self.frame.origin;
+        let b = a + self.height * self.frame.axis;
+        Aabb::new(a - r, a + r).surrounding(&Aabb::new(b - r, b + r))
+    }
+}
+
+/// Ring around `axis` through `center`: the points at `minor_radius` from the circle of
+/// `major_radius`. `u` goes around the axis, `v` around the tube.
+pub struct Torus {
+    frame: Frame,
+    major_radius: f64,
+    minor_radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Arc<dyn Material>,
+    ) -> Torus {
+        Torus {
+            frame: Frame::new(center, axis),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+
+    fn local_hit(&self, local: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<LocalHit> {
+        let (big_r, r) = (self.major_radius, self.minor_radius);
+
+        // the quartic is solved along a unit direction, starting where the ray enters the
+        // bounding sphere: close to the torus the coefficients stay small and well scaled
+        let length = local.dir().length();
+        let d = local.dir() / length;
+        let bound = big_r + r;
+        let b = local.origin().dot(d);
+        let c = local.origin().dot(local.origin()) - bound * bound;
+        if b * b - c < 0.0 {
+            return None;
+        }
+        let start = if c > 0.0 {
+            -b - (b * b - c).sqrt()
+        } else {
+            0.0
+        };
+        let o = local.origin() + start * d;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2) along p = o + s d
+        let f = o.dot(d);
+        let g = o.dot(o) + big_r * big_r - r * r;
+        let four_r2 = 4.0 * big_r * big_r;
+        let a2 = d.i * d.i + d.k * d.k;
+        let b2 = 2.0 * (o.i * d.i + o.k * d.k);
+        let c2 = o.i * o.i + o.k * o.k;
+        let roots = solve_quartic(
+            1.0,
+            4.0 * f,
+            4.0 * f * f + 2.0 * g - four_r2 * a2,
+            4.0 * f * g - four_r2 * b2,
+            g * g - four_r2 * c2,
+        );
+
+        let mut closest = None;
+        for s in roots {
+            let t = (s + start) / length;
+            if !in_range(t, ray_tmin, ray_tmax) {
+                continue;
+            }
+            let p = local.at(t);
+            let ring = (p.i * p.i + p.k * p.k).sqrt();
+            // from the closest point of the center circle
+            let normal = if ring > 0.0 {
+                (p - big_r * Vec3::new(p.i / ring, 0.0, p.k / ring)).unit_vector()
+            } else {
+                Vec3::new(0.0, p.j.signum(), 0.0)
+            };
+            keep_closest(
+                &mut closest,
+                LocalHit {
+                    t,
+                    normal,
+                    u: azimuth(p),
+                    v: p.j.atan2(ring - big_r) / (2.0 * PI) + 0.5,
+                },
+            );
+        }
+        closest
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        let local = self.frame.ray_to_local(ray);
+        let hit = self.local_hit(&local, ray_tmin, ray_tmax);
+        to_hit_record(&self.frame, ray, hit, &self.material)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::new(self.minor_radius, self.minor_radius, self.minor_radius);
+        let ring = self.frame.circle_box(0.0, self.major_radius);
+        Aabb::new(ring.min - r, ring.max + r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Lambertian, Rgb};
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Rgb::white()))
+    }
+
+    fn ray(origin: Point, direction: Vec3) -> Ray {
+        Ray::new(origin, direction)
+    }
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn test_cylinder() {
+        let base = Point::new(0.0, -1.0, 0.0);
+        let top = Point::new(0.0, 1.0, 0.0);
+        let open = Cylinder::new(base, top, 0.5, false, material());
+        let capped = Cylinder::new(base, top, 0.5, true, material());
+        let side = ray(Point::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -2.0));
+        let down = ray(Point::new(0.1, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        let hit_record = open.hit(&side, 0.0, f64::INFINITY).unwrap();
+        assert!((hit_record.t - 2.25).abs() < 1e-12 && hit_record.out_facing);
+        assert!(close(hit_record.normal, Vec3::new(0.0, 0.0, 1.0)));
+        assert!((hit_record.v - 0.5).abs() < 1e-12);
+
+        // an open tube is seen from inside through its end
+        let hit_record = open.hit(&down, 0.0, f64::INFINITY);
+        assert!(hit_record.is_none());
+
+        let hit_record = capped.hit(&down, 0.0, f64::INFINITY).unwrap();
+        assert!((hit_record.t - 4.0).abs() < 1e-12 && hit_record.out_facing);
+        assert!(close(hit_record.normal, Vec3::new(0.0, 1.0, 0.0)));
+
+        let bbox = capped.bounding_box();
+        assert!(bbox.min.j == -1.0 && bbox.max.j == 1.0 && bbox.max.i == 0.5);
+    }
+
+    #[test]
+    fn test_tilted_cylinder_inside() {
+        let cylinder = Cylinder::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(2.0, 2.0, 0.0),
+            0.5,
+            false,
+            material(),
+        );
+        // from the axis straight out, perpendicular to it
+        let out = ray(Point::new(1.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit_record = cylinder.hit(&out, 0.0, f64::INFINITY).unwrap();
+
+        assert!((hit_record.t - 0.5).abs() < 1e-12 && !hit_record.out_facing);
+        assert!(close(hit_record.normal, Vec3::new(0.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn test_cone() {
+        // 45 degree cone, apex at the top
+        let cone = Cone::new(
+            Point::new(0.0, 0.0, 0.0),
+            1.0,
+            Point::new(0.0, 1.0, 0.0),
+            0.0,
+            true,
+            material(),
+        );
+        let side = ray(Point::new(5.0, 0.5, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let up = ray(Point::new(0.2, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let above = ray(Point::new(0.0, 1.5, 5.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let hit_record = cone.hit(&side, 0.0, f64::INFINITY).unwrap();
+        assert!((hit_record.t - 4.5).abs() < 1e-12 && hit_record.out_facing);
+        let slant = Vec3::new(1.0, 1.0, 0.0).unit_vector();
+        assert!(close(hit_record.normal, slant));
+
+        let hit_record = cone.hit(&up, 0.0, f64::INFINITY).unwrap();
+        assert!((hit_record.t - 5.0).abs() < 1e-12);
+        assert!(close(hit_record.normal, Vec3::new(0.0, -1.0, 0.0)));
+
+        // the mirrored nappe above the apex isn't part of the shape
+        assert!(cone.hit(&above, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_capsule() {
+        let capsule = Capsule::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(0.0, 2.0, 0.0),
+            0.5,
+            material(),
+        );
+        let top = ray(Point::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let side = ray(Point::new(5.0, 1.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let corner = ray(Point::new(0.3, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+
+        let hit_record = capsule.hit(&top, 0.0, f64::INFINITY).unwrap();
+        assert!((hit_record.t - 2.5).abs() < 1e-12);
+        assert!(close(hit_record.normal, Vec3::new(0.0, 1.0, 0.0)));
+        assert!((hit_record.v - 1.0).abs() < 1e-12);
+
+        let hit_record = capsule.hit(&side, 0.0, f64::INFINITY).unwrap();
+        assert!((hit_record.t - 4.5).abs() < 1e-12);
+        assert!(close(hit_record.normal, Vec3::new(1.0, 0.0, 0.0)));
+
+        // on the bottom half sphere, 0.4 below its center
+        let hit_record = capsule.hit(&corner, 0.0, f64::INFINITY).unwrap();
+        assert!((hit_record.t - 4.6).abs() < 1e-12);
+        assert!(close(hit_record.normal, Vec3::new(0.6, -0.8, 0.0)));
+    }
+
+    #[test]
+    fn test_torus() {
+        let torus = Torus::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            2.0,
+            0.5,
+            material(),
+        );
+        let through = ray(Point::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hole = ray(Point::new(0.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let down = ray(Point::new(2.0, 10.0, 0.0), Vec3::new(0.0, -3.0, 0.0));
+
+        // enters the tube at x = -2.5
+        let hit_record = torus.hit(&through, 0.0, f64::INFINITY).unwrap();
+        assert!((hit_record.t - 7.5).abs() < 1e-9 && hit_record.out_facing);
+        assert!(close(hit_record.normal, Vec3::new(-1.0, 0.0, 0.0)));
+
+        // from inside the tube the next hit is its far side at x = -1.5
+        let hit_record = torus.hit(&through, 8.0, f64::INFINITY).unwrap();
+        assert!((hit_record.t - 8.5).abs() < 1e-9 && !hit_record.out_facing);
+
+        assert!(torus.hit(&hole, 0.0, f64::INFINITY).is_none());
+
+        let hit_record = torus.hit(&down, 0.0, f64::INFINITY).unwrap();
+        assert!((hit_record.t - 9.5 / 3.0).abs() < 1e-9);
+        assert!(close(hit_record.normal, Vec3::new(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_torus_far_away() {
+        // a thin ring seen from far, where a naive quartic loses every digit
+        let torus = Torus::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            0.01,
+            material(),
+        );
+        let grazing = ray(Point::new(1.0, 0.0, 1e4), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = torus.hit(&grazing, 0.0, f64::INFINITY).unwrap();
+
+        assert!((hit_record.t - (1e4 - 0.01)).abs() < 1e-6);
+        assert!(close(hit_record.normal, Vec3::new(0.0, 0.0, 1.0)));
+    }
+}
@@ -0,0 +1,358 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::{
+    aabb::Aabb,
+    bvh::BvhNode,
+    hittable::{HitRecord, Hittable, HittableList},
+    material::Material,
+    pdf::solid_angle_pdf,
+    random::RandomGenerator,
+    ray::Ray,
+    vec3::{Point, Vec3},
+};
+
+// flat triangles get boxes with zero thickness, pad them by this much
+const BBOX_PADDING: f64 = 1e-4;
+
+// Möller–Trumbore, returns t and the barycentric weights of b and c
+fn intersect(
+    a: Point,
+    b: Point,
+    c: Point,
+    ray: &Ray,
+    ray_tmin: f64,
+    ray_tmax: f64,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let pvec = ray.dir().cross(edge2);
+    let det = edge1.dot(pvec);
+
+    // the ray runs parallel to the triangle plane
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let tvec = ray.origin() - a;
+    let b1 = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(edge1);
+    let b2 = ray.dir().dot(qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(qvec) * inv_det;
+    if t <= ray_tmin || t >= ray_tmax {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+fn triangle_box(a: Point, b: Point, c: Point) -> Aabb {
+    Aabb::new(a, b)
+        .surrounding(&Aabb::new(c, c))
+        .padded(BBOX_PADDING)
+}
+
+/// A single triangle. `u` and `v` of the hit record are the barycentric weights of `b` and `c`.
+pub struct Triangle {
+    pub a: Point,
+    pub b: Point,
+    pub c: Point,
+    pub material: Arc<dyn Material>,
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        let (t, b1, b2) = intersect(self.a, self.b, self.c, ray, ray_tmin, ray_tmax)?;
+        let outside_normal = (self.b - self.a).cross(self.c - self.a).unit_vector();
+        let mut tmp = HitRecord {
+            intersection: ray.at(t),
+            t,
+            normal: Vec3::new(0.0, 0.0, 0.0),
+            out_facing: false,
+            u: b1,
+            v: b2,
+            material: self.material.clone(),
+        };
+
+        tmp.set_outside_normal(ray, outside_normal);
+
+        Some(tmp)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        triangle_box(self.a, self.b, self.c)
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        let area = 0.5 * (self.b - self.a).cross(self.c - self.a).length();
+        solid_angle_pdf(self, ray, area)
+    }
+
+    fn sample(&self, origin: Point, _: f64, random_generator: &mut RandomGenerator) -> Vec3 {
+        // the square root keeps the points from bunching up at `a`
+        let r1: f64 = random_generator.gen_range(0.0..1.0_f64).sqrt();
+        let r2: f64 = random_generator.gen_range(0.0..1.0);
+        let point = (1.0 - r1) * self.a + r1 * (1.0 - r2) * self.b + r1 * r2 * self.c;
+        point - origin
+    }
+}
+
+/// Vertex indices of one mesh face. Normals and uvs are indexed separately from positions, as
+/// in OBJ files, and are optional per face.
+#[derive(Clone, Copy, Debug)]
+pub struct MeshFace {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+struct MeshData {
+    positions: Vec<Point>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<MeshFace>,
+    material: Arc<dyn Material>,
+}
+
+// one face of a mesh, it only keeps a handle to the shared buffers
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: usize,
+}
+
+impl MeshTriangle {
+    fn vertices(&self) -> (Point, Point, Point) {
+        let [a, b, c] = self.mesh.faces[self.face].positions;
+        let positions = &self.mesh.positions;
+        (positions[a], positions[b], positions[c])
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        let (a, b, c) = self.vertices();
+        let (t, b1, b2) = intersect(a, b, c, ray, ray_tmin, ray_tmax)?;
+        let b0 = 1.0 - b1 - b2;
+        let face = &self.mesh.faces[self.face];
+
+        let (u, v) = match face.uvs {
+            Some([uv0, uv1, uv2]) => {
+                let uvs = &self.mesh.uvs;
+                (
+                    b0 * uvs[uv0].0 + b1 * uvs[uv1].0 + b2 * uvs[uv2].0,
+                    b0 * uvs[uv0].1 + b1 * uvs[uv1].1 + b2 * uvs[uv2].1,
+                )
+            }
+            None => (b1, b2),
+        };
+
+        let geometric_normal = (b - a).cross(c - a).unit_vector();
+        let mut tmp = HitRecord {
+            intersection: ray.at(t),
+            t,
+            normal: Vec3::new(0.0, 0.0, 0.0),
+            out_facing: false,
+            u,
+            v,
+            material: self.mesh.material.clone(),
+        };
+
+        // the side is decided by the real surface, the interpolated normal is only for shading
+        tmp.set_outside_normal(ray, geometric_normal);
+        if let Some([n0, n1, n2]) = face.normals {
+            let normals = &self.mesh.normals;
+            let mut shading_normal =
+                (b0 * normals[n0] + b1 * normals[n1] + b2 * normals[n2]).unit_vector();
+            if shading_normal.dot(geometric_normal) < 0.0 {
+                shading_normal = -shading_normal;
+            }
+            tmp.normal = if tmp.out_facing {
+                shading_normal
+            } else {
+                -shading_normal
+            };
+        }
+
+        Some(tmp)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let (a, b, c) = self.vertices();
+        triangle_box(a, b, c)
+    }
+}
+
+/// Indexed triangle mesh. Every face shares the vertex, normal and uv buffers, and the faces
+/// are kept in their own BVH so a mesh can be put into a scene like any other shape.
+pub struct TriangleMesh {
+    bvh: BvhNode,
+    faces_count: usize,
+}
+
+impl TriangleMesh {
+    // panics when a face refers to a vertex, normal or uv that doesn't exist
+    pub fn new(
+        positions: Vec<Point>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<MeshFace>,
+        material: Arc<dyn Material>,
+    ) -> TriangleMesh {
+        for face in &faces {
+            assert!(face.positions.iter().all(|&ix| ix < positions.len()));
+            assert!(face.normals.iter().flatten().all(|&ix| ix < normals.len()));
+            assert!(face.uvs.iter().flatten().all(|&ix| ix < uvs.len()));
+        }
+
+        let faces_count = faces.len();
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            faces,
+            material,
+        });
+        let triangles: HittableList = (0..faces_count)
+            .map(|face| {
+                Arc::new(MeshTriangle {
+                    mesh: mesh.clone(),
+                    face,
+                }) as Arc<dyn Hittable>
+            })
+            .collect();
+
+        TriangleMesh {
+            bvh: BvhNode::new(triangles),
+            faces_count,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.faces_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces_count == 0
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, ray_tmin: f64, ray_tmax: f64) -> Option<HitRecord> {
+        self.bvh.hit(ray, ray_tmin, ray_tmax)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Lambertian, Rgb};
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Rgb::new(0.5, 0.5, 0.5)))
+    }
+
+    fn triangle() -> Triangle {
+        Triangle {
+            a: Point::new(0.0, 0.0, 0.0),
+            b: Point::new(1.0, 0.0, 0.0),
+            c: Point::new(0.0, 1.0, 0.0),
+            material: material(),
+        }
+    }
+
+    #[test]
+    fn test_triangle_barycentric() {
+        let ray = Ray::new(Point::new(0.25, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = triangle().hit(&ray, 0.001, f64::INFINITY).unwrap();
+
+        assert!(rec.t == 1.0);
+        assert!(rec.u == 0.25 && rec.v == 0.5);
+        assert!(rec.out_facing && rec.normal.k == 1.0);
+    }
+
+    #[test]
+    fn test_triangle_back_face() {
+        let ray = Ray::new(Point::new(0.25, 0.25, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = triangle().hit(&ray, 0.001, f64::INFINITY).unwrap();
+
+        assert!(!rec.out_facing && rec.normal.k == -1.0);
+    }
+
+    #[test]
+    fn test_triangle_miss() {
+        let outside = Ray::new(Point::new(0.75, 0.75, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let parallel = Ray::new(Point::new(0.0, 0.0, -1.0), Vec3::new(1.0, 1.0, 0.0));
+
+        assert!(triangle().hit(&outside, 0.001, f64::INFINITY).is_none());
+        assert!(triangle().hit(&parallel, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_mesh_interpolates_normals_and_uvs() {
+        let mesh = TriangleMesh::new(
+            vec![
+                Point::new(0.0, 0.0, 0.0),
+                Point::new(1.0, 0.0, 0.0),
+                Point::new(0.0, 1.0, 0.0),
+                Point::new(1.0, 1.0, 0.0),
+            ],
+            vec![Vec3::new(0.0, 0.0, -1.0), Vec3::new(1.0, 0.0, -1.0)],
+            vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            vec![
+                MeshFace {
+                    positions: [0, 1, 2],
+                    normals: Some([0, 1, 0]),
+                    uvs: Some([0, 1, 2]),
+                },
+                MeshFace {
+                    positions: [1, 3, 2],
+                    normals: None,
+                    uvs: None,
+                },
+            ],
+            material(),
+        );
+        let ray = Ray::new(Point::new(0.5, 0.25, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = mesh.hit(&ray, 0.001, f64::INFINITY).unwrap();
+
+        assert!(mesh.len() == 2);
+        assert!(rec.u == 0.5 && rec.v == 0.25);
+        assert!(!rec.out_facing && rec.normal.i > 0.0 && rec.normal.k < 0.0);
+        assert!((rec.normal.length() - 1.0).abs() < 1e-12);
+
+        let ray = Ray::new(Point::new(0.75, 0.75, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = mesh.hit(&ray, 0.001, f64::INFINITY).unwrap();
+
+        assert!(rec.normal.i == 0.0 && rec.normal.k == -1.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mesh_bad_index() {
+        TriangleMesh::new(
+            vec![Point::new(0.0, 0.0, 0.0)],
+            vec![],
+            vec![],
+            vec![MeshFace {
+                positions: [0, 1, 2],
+                normals: None,
+                uvs: None,
+            }],
+            material(),
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use std::{fs::File, io::BufReader, path::Path, sync::Arc};
+
+use image::{codecs::hdr::HdrDecoder, ColorType, ImageError, ImageResult};
+
+use crate::{material::Rgb, vec3::Point};
+
+mod noise;
+
+pub use noise::{Marble, Noise, Perlin, UvGrid, Voronoi, Wood, Worley};
+
+/// A color varying over a surface, looked up by the hit's surface coordinates `(u, v)` or by
+/// the hit point itself for solid textures.
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, point: Point) -> Rgb;
+}
+
+/// A solid color, the same everywhere.
+impl Texture for Rgb {
+    fn value(&self, _: f64, _: f64, _: Point) -> Rgb {
+        *self
+    }
+}
+
+/// 3D checkerboard of cubes with side `scale`, alternating between `even` and `odd`. Being
+/// solid it doesn't care about uvs and doesn't stretch on curved shapes.
+pub struct Checker {
+    pub scale: f64,
+    pub even: Arc<dyn Texture>,
+    pub odd: Arc<dyn Texture>,
+}
+
+impl Checker {
+    pub fn new(scale: f64, even: impl Texture + 'static, odd: impl Texture + 'static) -> Checker {
+        Checker {
+            scale,
+            even: Arc::new(even),
+            odd: Arc::new(odd),
+        }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, point: Point) -> Rgb {
+        let cell = (point.i / self.scale).floor()
+            + (point.j / self.scale).floor()
+            + (point.k / self.scale).floor();
+
+        if cell.rem_euclid(2.0) == 0.0 {
+            self.even.value(u, v, point)
+        } else {
+            self.odd.value(u, v, point)
+        }
+    }
+}
+
+/// A picture wrapped with the uvs, `v = 0` is the bottom row. Lookups outside [0, 1] are
+/// clamped to the border.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl ImageTexture {
+    // `pixels` are linear, row by row from the top
+    pub fn new(width: usize, height: usize, pixels: Vec<Rgb>) -> ImageTexture {
+        assert!(width > 0 && height > 0 && pixels.len() == width * height);
+        ImageTexture {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> ImageResult<ImageTexture> {
+        let (width, height, pixels) = load_linear(path)?;
+        Ok(ImageTexture::new(width, height, pixels))
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _: Point) -> Rgb {
+        let u = u.clamp(0.0, 1.0);
+        let v = 1.0 - v.clamp(0.0, 1.0);
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+// the inverse of `Rgb::to_srgb`
+fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Reads an image as linear colors, row by row from the top. Float formats like `.hdr` and
+/// `.exr` are taken as they are, 8 and 16-bit ones are decoded from sRGB.
+pub fn load_linear(path: impl AsRef<Path>) -> ImageResult<(usize, usize, Vec<Rgb>)> {
+    let path = path.as_ref();
+    let is_hdr = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("hdr"));
+
+    // `image::open` squashes .hdr files to 8 bits, decode the floats directly
+    if is_hdr {
+        let file = File::open(path).map_err(ImageError::IoError)?;
+        let decoder = HdrDecoder::new(BufReader::new(file))?;
+        let metadata = decoder.metadata();
+        let pixels = decoder
+            .read_image_hdr()?
+            .into_iter()
+            .map(|p| Rgb::new(p.0[0] as f64, p.0[1] as f64, p.0[2] as f64))
+            .collect();
+        return Ok((metadata.width as usize, metadata.height as usize, pixels));
+    }
+
+    let image = image::open(path)?;
+    let is_float = matches!(image.color(), ColorType::Rgb32F | ColorType::Rgba32F);
+    let image = image.into_rgb32f();
+    let decode = |c: f32| {
+        if is_float {
+            c as f64
+        } else {
+            srgb_to_linear(c as f64)
+        }
+    };
+    let pixels = image
+        .pixels()
+        .map(|p| Rgb::new(decode(p.0[0]), decode(p.0[1]), decode(p.0[2])))
+        .collect();
+    Ok((image.width() as usize, image.height() as usize, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solid() {
+        let c1 = Rgb::new(0.1, 0.2, 0.3).value(0.5, 0.5, Point::new(1.0, 2.0, 3.0));
+
+        assert!(c1.r == 0.1 && c1.g == 0.2 && c1.b == 0.3);
+    }
+
+    #[test]
+    fn test_checker() {
+        let checker = Checker::new(1.0, Rgb::white(), Rgb::new(0.0, 0.0, 0.0));
+        let c1 = checker.value(0.0, 0.0, Point::new(0.5, 0.5, 0.5));
+        let c2 = checker.value(0.0, 0.0, Point::new(1.5, 0.5, 0.5));
+        let c3 = checker.value(0.0, 0.0, Point::new(-0.5, 0.5, 0.5));
+        let c4 = checker.value(0.0, 0.0, Point::new(1.5, -0.5, 0.5));
+
+        assert!(c1.r == 1.0 && c2.r == 0.0 && c3.r == 0.0 && c4.r == 1.0);
+    }
+
+    #[test]
+    fn test_image_lookup() {
+        // top row red, bottom row blue
+        let texture = ImageTexture::new(
+            2,
+            2,
+            vec![
+                Rgb::new(1.0, 0.0, 0.0),
+                Rgb::new(1.0, 0.0, 0.0),
+                Rgb::new(0.0, 0.0, 1.0),
+                Rgb::new(0.0, 0.0, 1.0),
+            ],
+        );
+        let origin = Point::new(0.0, 0.0, 0.0);
+
+        assert!(texture.value(0.25, 0.75, origin).r == 1.0);
+        assert!(texture.value(0.75, 0.25, origin).b == 1.0);
+        assert!(texture.value(2.0, -1.0, origin).b == 1.0);
+    }
+
+    #[test]
+    fn test_load_decodes_srgb() {
+        let dir = std::env::temp_dir().join("rtoneweekend_texture_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.png");
+        image::save_buffer(&path, &[255, 188, 0], 1, 1, ColorType::Rgb8).unwrap();
+        let texture = ImageTexture::load(&path).unwrap();
+        let c1 = texture.value(0.5, 0.5, Point::new(0.0, 0.0, 0.0));
+
+        assert!(c1.r == 1.0 && c1.b == 0.0);
+        assert!((c1.g - 0.5).abs() < 0.01);
+    }
+}
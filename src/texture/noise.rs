@@ -0,0 +1,407 @@
+use rand::Rng;
+
+use super::Texture;
+use crate::{
+    material::Rgb,
+    random::RandomGenerator,
+    vec3::{Point, Vec3},
+};
+
+const POINT_COUNT: usize = 256;
+
+fn permutation(random_generator: &mut RandomGenerator) -> Vec<usize> {
+    let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+    for i in (1..POINT_COUNT).rev() {
+        let target = random_generator.gen_range(0..=i);
+        perm.swap(i, target);
+    }
+    perm
+}
+
+// hashes integer lattice points into [0, POINT_COUNT) with one permutation per axis
+struct Lattice {
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Lattice {
+    fn new(random_generator: &mut RandomGenerator) -> Lattice {
+        Lattice {
+            perm_x: permutation(random_generator),
+            perm_y: permutation(random_generator),
+            perm_z: permutation(random_generator),
+        }
+    }
+
+    fn hash(&self, i: i64, j: i64, k: i64) -> usize {
+        self.perm_x[(i & 255) as usize]
+            ^ self.perm_y[(j & 255) as usize]
+            ^ self.perm_z[(k & 255) as usize]
+    }
+}
+
+fn lerp(a: Rgb, b: Rgb, t: f64) -> Rgb {
+    (1.0 - t) * a + t * b
+}
+
+/// Gradient noise on the integer lattice, with random unit gradients shuffled by three
+/// permutation tables. The same seed always gives the same noise.
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    lattice: Lattice,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut random_generator = RandomGenerator::new(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| Vec3::random_unit_vector(&mut random_generator))
+            .collect();
+
+        Perlin {
+            gradients,
+            lattice: Lattice::new(&mut random_generator),
+        }
+    }
+
+    /// Smooth noise in [-1, 1], zero on every lattice point.
+    pub fn noise(&self, p: Point) -> f64 {
+        let (i, j, k) = (p.i.floor(), p.j.floor(), p.k.floor());
+        let (u, v, w) = (p.i - i, p.j - j, p.k - k);
+        // Hermite smoothing hides the lattice
+        let (uu, vv, ww) = (
+            u * u * (3.0 - 2.0 * u),
+            v * v * (3.0 - 2.0 * v),
+            w * w * (3.0 - 2.0 * w),
+        );
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = self.gradients[self.lattice.hash(
+                        i as i64 + di,
+                        j as i64 + dj,
+                        k as i64 + dk,
+                    )];
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let weight = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * gradient.dot(weight);
+                }
+            }
+        }
+        accum
+    }
+
+    /// Sum of `octaves` layers of noise, each at twice the frequency and half the amplitude
+    /// of the previous one. Folding every layer with `abs` gives the billowy look of smoke
+    /// and the veins of marble.
+    pub fn turbulence(&self, p: Point, octaves: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+
+        for _ in 0..octaves {
+            accum += weight * self.noise(p).abs();
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+        accum
+    }
+}
+
+/// Plain turbulence scaling `color`, cloudy and smoke-like.
+pub struct Noise {
+    pub perlin: Perlin,
+    pub frequency: f64,
+    pub octaves: u32,
+    pub color: Rgb,
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Noise {
+        Noise {
+            perlin: Perlin::new(seed),
+            frequency: 4.0,
+            octaves: 7,
+            color: Rgb::white(),
+        }
+    }
+}
+
+impl Texture for Noise {
+    fn value(&self, _: f64, _: f64, point: Point) -> Rgb {
+        let turbulence = self.perlin.turbulence(self.frequency * point, self.octaves);
+        self.color * turbulence.min(1.0)
+    }
+}
+
+/// Bands along k whose phase is shifted by turbulence, the classic Perlin marble.
+pub struct Marble {
+    pub perlin: Perlin,
+    pub frequency: f64,
+    pub octaves: u32,
+    pub light: Rgb,
+    pub dark: Rgb,
+}
+
+impl Marble {
+    pub fn new(seed: u64) -> Marble {
+        Marble {
+            perlin: Perlin::new(seed),
+            frequency: 4.0,
+            octaves: 7,
+            light: Rgb::new(0.9, 0.9, 0.88),
+            dark: Rgb::new(0.15, 0.15, 0.2),
+        }
+    }
+}
+
+impl Texture for Marble {
+    fn value(&self, _: f64, _: f64, point: Point) -> Rgb {
+        let turbulence = self.perlin.turbulence(point, self.octaves);
+        let t = 0.5 * (1.0 + (self.frequency * point.k + 10.0 * turbulence).sin());
+        lerp(self.dark, self.light, t)
+    }
+}
+
+/// Concentric rings around the j axis, wobbled a little by turbulence.
+pub struct Wood {
+    pub perlin: Perlin,
+    pub frequency: f64, // rings per unit of radius
+    pub octaves: u32,
+    pub light: Rgb,
+    pub dark: Rgb,
+}
+
+impl Wood {
+    pub fn new(seed: u64) -> Wood {
+        Wood {
+            perlin: Perlin::new(seed),
+            frequency: 8.0,
+            octaves: 4,
+            light: Rgb::new(0.75, 0.5, 0.25),
+            dark: Rgb::new(0.4, 0.2, 0.07),
+        }
+    }
+}
+
+impl Texture for Wood {
+    fn value(&self, _: f64, _: f64, point: Point) -> Rgb {
+        let radius = (point.i * point.i + point.k * point.k).sqrt();
+        let turbulence = self.perlin.turbulence(2.0 * point, self.octaves);
+        let rings = (self.frequency * radius + 0.5 * turbulence).fract();
+        // sharp dark edge on the outside of each ring
+        lerp(self.light, self.dark, rings * rings * rings)
+    }
+}
+
+/// Worley's cellular noise: one random feature point per lattice cell.
+pub struct Worley {
+    points: Vec<Vec3>, // offset of the feature point inside its cell
+    lattice: Lattice,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Worley {
+        let mut random_generator = RandomGenerator::new(seed);
+        let points = (0..POINT_COUNT)
+            .map(|_| {
+                Vec3::new(
+                    random_generator.gen_range(0.0..1.0),
+                    random_generator.gen_range(0.0..1.0),
+                    random_generator.gen_range(0.0..1.0),
+                )
+            })
+            .collect();
+
+        Worley {
+            points,
+            lattice: Lattice::new(&mut random_generator),
+        }
+    }
+
+    /// Distance from `p` to the closest feature point, mostly below 1.
+    pub fn distance(&self, p: Point) -> f64 {
+        let (i, j, k) = (p.i.floor() as i64, p.j.floor() as i64, p.k.floor() as i64);
+        let mut closest = f64::INFINITY;
+
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let (ci, cj, ck) = (i + di, j + dj, k + dk);
+                    let feature = Point::new(ci as f64, cj as f64, ck as f64)
+                        + self.points[self.lattice.hash(ci, cj, ck)];
+                    closest = closest.min((feature - p).length());
+                }
+            }
+        }
+        closest
+    }
+}
+
+/// Voronoi cells, the Worley distance blended from `center` to `edge`. Every octave adds
+/// cells twice as small.
+pub struct Voronoi {
+    pub worley: Worley,
+    pub frequency: f64,
+    pub octaves: u32,
+    pub center: Rgb,
+    pub edge: Rgb,
+}
+
+impl Voronoi {
+    pub fn new(seed: u64) -> Voronoi {
+        Voronoi {
+            worley: Worley::new(seed),
+            frequency: 4.0,
+            octaves: 1,
+            center: Rgb::new(0.1, 0.3, 0.6),
+            edge: Rgb::new(0.9, 0.95, 1.0),
+        }
+    }
+}
+
+impl Texture for Voronoi {
+    fn value(&self, _: f64, _: f64, point: Point) -> Rgb {
+        let mut accum = 0.0;
+        let mut total_weight = 0.0;
+        let mut p = self.frequency * point;
+        let mut weight = 1.0;
+
+        for _ in 0..self.octaves {
+            accum += weight * self.worley.distance(p);
+            total_weight += weight;
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+        let t = if total_weight > 0.0 {
+            (accum / total_weight).min(1.0)
+        } else {
+            0.0
+        };
+        lerp(self.center, self.edge, t)
+    }
+}
+
+/// Debug view of the uvs: red grows with u, green with v, and white lines split the uv square
+/// in `frequency` cells per side. Every further octave splits each cell in two with fainter
+/// lines, so stretching and seams show at a glance.
+pub struct UvGrid {
+    pub frequency: f64,
+    pub octaves: u32,
+}
+
+impl Default for UvGrid {
+    fn default() -> UvGrid {
+        UvGrid {
+            frequency: 8.0,
+            octaves: 2,
+        }
+    }
+}
+
+impl Texture for UvGrid {
+    fn value(&self, u: f64, v: f64, _: Point) -> Rgb {
+        let base = Rgb::new(u.rem_euclid(1.0), v.rem_euclid(1.0), 0.25);
+        let mut cells = self.frequency;
+        let mut line = 1.0;
+
+        for _ in 0..self.octaves {
+            // lines keep the same width in uv whatever the octave
+            let half_width = 0.01 * cells / self.frequency;
+            let (du, dv) = ((u * cells).fract().abs(), (v * cells).fract().abs());
+            let near = |d: f64| d < half_width || d > 1.0 - half_width;
+            if near(du) || near(dv) {
+                return lerp(base, Rgb::white(), line);
+            }
+            cells *= 2.0;
+            line *= 0.5;
+        }
+        base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points() -> Vec<Point> {
+        let mut random_generator = RandomGenerator::new(7);
+        (0..200)
+            .map(|_| 10.0 * Vec3::random_unit_vector(&mut random_generator))
+            .collect()
+    }
+
+    #[test]
+    fn test_perlin_range_and_lattice() {
+        let perlin = Perlin::new(1);
+
+        assert!(perlin.noise(Point::new(3.0, -2.0, 5.0)) == 0.0);
+        assert!(points().into_iter().all(|p| perlin.noise(p).abs() <= 1.0));
+        assert!(points().into_iter().any(|p| perlin.noise(p).abs() > 0.1));
+    }
+
+    #[test]
+    fn test_perlin_is_seeded() {
+        let (p1, p2, p3) = (Perlin::new(1), Perlin::new(1), Perlin::new(2));
+        let p = Point::new(0.3, 1.7, -2.2);
+
+        assert!(p1.noise(p) == p2.noise(p));
+        assert!(p1.noise(p) != p3.noise(p));
+    }
+
+    #[test]
+    fn test_turbulence_octaves() {
+        let perlin = Perlin::new(1);
+        let p = Point::new(0.3, 1.7, -2.2);
+
+        assert!(perlin.turbulence(p, 0) == 0.0);
+        assert!(perlin.turbulence(p, 1) == perlin.noise(p).abs());
+        assert!(points().into_iter().all(|p| perlin.turbulence(p, 7) < 2.0));
+    }
+
+    #[test]
+    fn test_procedural_colors_in_range() {
+        let textures: Vec<Box<dyn Texture>> = vec![
+            Box::new(Noise::new(1)),
+            Box::new(Marble::new(1)),
+            Box::new(Wood::new(1)),
+            Box::new(Voronoi::new(1)),
+            Box::new(UvGrid::default()),
+        ];
+
+        for texture in textures {
+            for p in points() {
+                let c = texture.value(p.i.rem_euclid(1.0), p.j.rem_euclid(1.0), p);
+                assert!([c.r, c.g, c.b].iter().all(|x| (0.0..=1.0).contains(x)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_worley_is_zero_on_features() {
+        let worley = Worley::new(3);
+        let feature = Point::new(2.0, -1.0, 0.0) + worley.points[worley.lattice.hash(2, -1, 0)];
+
+        assert!(worley.distance(feature) == 0.0);
+        assert!(points().into_iter().all(|p| worley.distance(p) < 2.0));
+    }
+
+    #[test]
+    fn test_uv_grid_lines() {
+        let grid = UvGrid::default();
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let on_line = grid.value(0.125, 0.3, origin);
+        let on_fine_line = grid.value(0.0625, 0.3, origin);
+        let inside = grid.value(0.09, 0.3, origin);
+
+        assert!(on_line.b == 1.0);
+        assert!(on_fine_line.b > 0.25 && on_fine_line.b < 1.0);
+        assert!(inside.b == 0.25 && inside.r == 0.09);
+    }
+}
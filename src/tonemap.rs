@@ -0,0 +1,194 @@
+use std::{fmt, str::FromStr};
+
+use serde::Deserialize;
+
+use crate::material::Rgb;
+
+/// Curves squeezing the unbounded scene radiance into the [0, 1] display range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMapper {
+    Clamp, // hard clip at 1, the highlights lose their detail and hue
+    Reinhard,
+    ReinhardExtended, // Reinhard that maps `white_point` to exactly 1
+    Aces,             // Narkowicz's fit of the ACES filmic curve, the default
+    Hable,            // Uncharted 2 filmic curve
+}
+
+impl ToneMapper {
+    pub const NAMES: [&'static str; 5] =
+        ["clamp", "reinhard", "reinhard_extended", "aces", "hable"];
+}
+
+impl FromStr for ToneMapper {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ToneMapper, String> {
+        match s {
+            "clamp" => Ok(ToneMapper::Clamp),
+            "reinhard" => Ok(ToneMapper::Reinhard),
+            "reinhard_extended" => Ok(ToneMapper::ReinhardExtended),
+            "aces" => Ok(ToneMapper::Aces),
+            "hable" => Ok(ToneMapper::Hable),
+            _ => Err(format!(
+                "unknown tone mapper `{}`, expected one of {}",
+                s,
+                ToneMapper::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ToneMapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ix = match self {
+            ToneMapper::Clamp => 0,
+            ToneMapper::Reinhard => 1,
+            ToneMapper::ReinhardExtended => 2,
+            ToneMapper::Aces => 3,
+            ToneMapper::Hable => 4,
+        };
+        f.write_str(ToneMapper::NAMES[ix])
+    }
+}
+
+/// How the linear framebuffer is turned into display values for 8-bit outputs.
+#[derive(Clone, Copy, Debug)]
+pub struct ToneMapping {
+    pub exposure: f64, // in stops, every +1 doubles the brightness
+    pub tone_mapper: ToneMapper,
+    pub white_point: f64, // smallest luminance mapped to white by `ReinhardExtended`
+}
+
+impl Default for ToneMapping {
+    fn default() -> ToneMapping {
+        ToneMapping {
+            exposure: 0.0,
+            // a filmic roll-off, bright lights and highlights fade to white instead of clipping
+            tone_mapper: ToneMapper::Aces,
+            white_point: 4.0,
+        }
+    }
+}
+
+// the Uncharted 2 partial curve
+fn hable_partial(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+fn per_channel(color: Rgb, curve: impl Fn(f64) -> f64) -> Rgb {
+    Rgb::new(curve(color.r), curve(color.g), curve(color.b))
+}
+
+impl ToneMapping {
+    /// Maps a linear color to linear display values in [0, 1], the sRGB encoding comes after.
+    pub fn apply(&self, color: Rgb) -> Rgb {
+        let color = color * 2f64.powf(self.exposure);
+        let mapped = match self.tone_mapper {
+            ToneMapper::Clamp => color,
+            // the Reinhard curves act on luminance, scaling the color keeps its hue
+            ToneMapper::Reinhard | ToneMapper::ReinhardExtended => {
+                let luminance = color.luminance();
+                if luminance <= 0.0 {
+                    return Rgb::new(0.0, 0.0, 0.0);
+                }
+                let white2 = match self.tone_mapper {
+                    ToneMapper::Reinhard => f64::INFINITY,
+                    _ => self.white_point * self.white_point,
+                };
+                let mapped_luminance = luminance * (1.0 + luminance / white2) / (1.0 + luminance);
+                color * (mapped_luminance / luminance)
+            }
+            ToneMapper::Aces => per_channel(color, |x| {
+                let x = x.max(0.0);
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+            }),
+            ToneMapper::Hable => {
+                let exposure_bias = 2.0;
+                let white_scale = 1.0 / hable_partial(11.2);
+                per_channel(color, |x| {
+                    hable_partial(x.max(0.0) * exposure_bias) * white_scale
+                })
+            }
+        };
+
+        per_channel(mapped, |x| x.clamp(0.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(tone_mapper: ToneMapper) -> ToneMapping {
+        ToneMapping {
+            tone_mapper,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_clamp() {
+        let c1 = mapping(ToneMapper::Clamp).apply(Rgb::new(0.5, 2.0, -1.0));
+
+        assert!(c1.r == 0.5 && c1.g == 1.0 && c1.b == 0.0);
+    }
+
+    #[test]
+    fn test_exposure() {
+        let tone_mapping = ToneMapping {
+            exposure: 1.0,
+            ..mapping(ToneMapper::Clamp)
+        };
+        let c1 = tone_mapping.apply(Rgb::new(0.25, 0.125, 0.0));
+
+        assert!(c1.r == 0.5 && c1.g == 0.25 && c1.b == 0.0);
+    }
+
+    // the highlights keep some detail unless clamping is asked for
+    #[test]
+    fn test_default_rolls_off() {
+        let bright = ToneMapping::default().apply(Rgb::white() * 2.0);
+        let brighter = ToneMapping::default().apply(Rgb::white() * 4.0);
+
+        assert!(bright.g < brighter.g && brighter.g < 1.0);
+    }
+
+    #[test]
+    fn test_reinhard() {
+        let c1 = mapping(ToneMapper::Reinhard).apply(Rgb::white());
+        let c2 = mapping(ToneMapper::Reinhard).apply(Rgb::white() * 1000.0);
+
+        assert!((c1.r - 0.5).abs() < 1e-12 && (c1.b - 0.5).abs() < 1e-12);
+        assert!(c2.g < 1.0 && c2.g > 0.99);
+    }
+
+    #[test]
+    fn test_reinhard_extended_white_point() {
+        let c1 = mapping(ToneMapper::ReinhardExtended).apply(Rgb::white() * 4.0);
+
+        assert!((c1.r - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_filmic_curves_roll_off() {
+        for tone_mapper in [ToneMapper::Aces, ToneMapper::Hable] {
+            let mut previous = -1.0;
+            for x in [0.0, 0.1, 0.5, 1.0, 2.0, 4.0] {
+                let mapped = mapping(tone_mapper).apply(Rgb::white() * x).r;
+                assert!(mapped > previous && mapped <= 1.0);
+                previous = mapped;
+            }
+            assert!(mapping(tone_mapper).apply(Rgb::new(0.0, 0.0, 0.0)).r.abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_from_str() {
+        for name in ToneMapper::NAMES {
+            assert!(name.parse::<ToneMapper>().unwrap().to_string() == name);
+        }
+        assert!("filmic".parse::<ToneMapper>().is_err());
+    }
+}
@@ -1,9 +1,9 @@
 #![allow(dead_code)]
-use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::ops::{Add, Div, Index, Mul, Neg, Sub};
 
-use rand::{rngs::ThreadRng, Rng};
+use rand::Rng;
 
-use crate::material::Rgb;
+use crate::{material::Rgb, random::RandomGenerator};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Vec3 {
@@ -17,7 +17,7 @@ impl Vec3 {
         Vec3 { i, j, k }
     }
 
-    pub fn random_unit_vector(random_generator: &mut ThreadRng) -> Vec3 {
+    pub fn random_unit_vector(random_generator: &mut RandomGenerator) -> Vec3 {
         loop {
             let p = Vec3 {
                 i: random_generator.gen_range(-1.0..1.0),
@@ -30,6 +30,18 @@ impl Vec3 {
         }
     }
 
+    // around +k with a density of cos(theta) / pi, the bounce of a perfectly diffuse surface
+    pub fn random_cosine_direction(random_generator: &mut RandomGenerator) -> Vec3 {
+        let r1: f64 = random_generator.gen_range(0.0..1.0);
+        let r2: f64 = random_generator.gen_range(0.0..1.0);
+        let phi = 2.0 * std::f64::consts::PI * r1;
+        Vec3::new(
+            phi.cos() * r2.sqrt(),
+            phi.sin() * r2.sqrt(),
+            (1.0 - r2).sqrt(),
+        )
+    }
+
     pub fn dot(&self, rhs: Vec3) -> f64 {
         self.i * rhs.i + self.j * rhs.j + self.k * rhs.k
     }
@@ -60,6 +72,19 @@ impl Vec3 {
         }
     }
 
+    // two unit vectors perpendicular to `self` (a unit vector) and to each other, so that
+    // (tangent, bitangent, self) is right-handed
+    pub fn tangent_frame(self) -> (Vec3, Vec3) {
+        let helper = if self.i.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let bitangent = self.cross(helper).unit_vector();
+        let tangent = bitangent.cross(self);
+        (tangent, bitangent)
+    }
+
     pub fn near_zero(&self) -> bool {
         let s = 1e-8;
         self.i < s && self.j < s && self.k < s
@@ -75,7 +100,7 @@ impl Vec3 {
         v_out_parp + v_out_perp
     }
 
-    pub fn random_in_unit_circle(random_generator: &mut ThreadRng) -> Vec3 {
+    pub fn random_in_unit_circle(random_generator: &mut RandomGenerator) -> Vec3 {
         loop {
             let p = Vec3::new(
                 random_generator.gen_range(-1.0..1.0),
@@ -101,12 +126,12 @@ impl Default for Point {
     }
 }
 
-impl Into<Rgb> for Vec3 {
-    fn into(self) -> Rgb {
+impl From<Vec3> for Rgb {
+    fn from(v: Vec3) -> Rgb {
         Rgb {
-            r: self.i,
-            g: self.j,
-            b: self.k,
+            r: v.i,
+            g: v.j,
+            b: v.k,
         }
     }
 }
@@ -171,6 +196,20 @@ impl Div<f64> for Vec3 {
     }
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    // axis 0, 1, 2 map to i, j, k
+    fn index(&self, axis: usize) -> &Self::Output {
+        match axis {
+            0 => &self.i,
+            1 => &self.j,
+            2 => &self.k,
+            _ => panic!("Vec3 axis out of range: {}", axis),
+        }
+    }
+}
+
 impl Neg for Vec3 {
     type Output = Vec3;
 
@@ -183,6 +222,405 @@ impl Neg for Vec3 {
     }
 }
 
+/// Row-major 4x4 matrix, applied to column vectors in homogeneous coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Matrix4 {
+        Matrix4 { m }
+    }
+
+    pub fn identity() -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (ix, row) in m.iter_mut().enumerate() {
+            row[ix] = 1.0;
+        }
+        Matrix4 { m }
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in self.m.iter().enumerate() {
+            for (col, &value) in values.iter().enumerate() {
+                m[col][row] = value;
+            }
+        }
+        Matrix4 { m }
+    }
+
+    // of the upper 3x3, how much the transform scales volumes
+    pub fn determinant3(&self) -> f64 {
+        let row = |ix: usize| Vec3::new(self.m[ix][0], self.m[ix][1], self.m[ix][2]);
+        row(0).cross(row(1)).dot(row(2))
+    }
+
+    // Gauss-Jordan elimination with partial pivoting, `None` when the matrix is singular
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut a = self.m;
+        let mut inverse = Matrix4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&x, &y| a[x][col].abs().total_cmp(&a[y][col].abs()))
+                .unwrap();
+            if a[pivot][col] == 0.0 || a[pivot][col].is_nan() {
+                return None;
+            }
+            a.swap(col, pivot);
+            inverse.swap(col, pivot);
+
+            let scale = a[col][col];
+            a[col] = a[col].map(|value| value / scale);
+            inverse[col] = inverse[col].map(|value| value / scale);
+            for row in (0..4).filter(|&row| row != col) {
+                let factor = a[row][col];
+                for c in 0..4 {
+                    a[row][c] -= factor * a[col][c];
+                    inverse[row][c] -= factor * inverse[col][c];
+                }
+            }
+        }
+        Some(Matrix4 { m: inverse })
+    }
+
+    // w = 1, translated
+    fn mul_point(&self, p: Point) -> Point {
+        let m = &self.m;
+        Point::new(
+            m[0][0] * p.i + m[0][1] * p.j + m[0][2] * p.k + m[0][3],
+            m[1][0] * p.i + m[1][1] * p.j + m[1][2] * p.k + m[1][3],
+            m[2][0] * p.i + m[2][1] * p.j + m[2][2] * p.k + m[2][3],
+        )
+    }
+
+    // w = 0, not translated
+    fn mul_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.i + m[0][1] * v.j + m[0][2] * v.k,
+            m[1][0] * v.i + m[1][1] * v.j + m[1][2] * v.k,
+            m[2][0] * v.i + m[2][1] * v.j + m[2][2] * v.k,
+        )
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Matrix4) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (col, value) in values.iter_mut().enumerate() {
+                *value = (0..4).map(|ix| self.m[row][ix] * rhs.m[ix][col]).sum();
+            }
+        }
+        Matrix4 { m }
+    }
+}
+
+/// An invertible affine transform, kept together with its inverse so rays can be taken into
+/// object space and normals brought back out without inverting anything per hit.
+#[derive(Copy, Clone, Debug)]
+pub struct Transform {
+    pub matrix: Matrix4,
+    pub inverse: Matrix4,
+}
+
+impl Transform {
+    // `None` when the matrix can't be inverted, like a scale by zero
+    pub fn new(matrix: Matrix4) -> Option<Transform> {
+        let inverse = matrix.inverse()?;
+        Some(Transform { matrix, inverse })
+    }
+
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    pub fn translation(offset: Vec3) -> Transform {
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for axis in 0..3 {
+            matrix.m[axis][3] = offset[axis];
+            inverse.m[axis][3] = -offset[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    // a factor of zero flattens everything and gives a transform that can't be undone
+    pub fn scaling(factors: Vec3) -> Transform {
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for axis in 0..3 {
+            matrix.m[axis][axis] = factors[axis];
+            inverse.m[axis][axis] = 1.0 / factors[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    // counterclockwise by `degrees` seen from the tip of `axis`, which doesn't need to be a
+    // unit vector
+    pub fn rotation(axis: Vec3, degrees: f64) -> Transform {
+        let a = axis.unit_vector();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1.0 - cos;
+        let matrix = Matrix4::new([
+            [
+                t * a.i * a.i + cos,
+                t * a.i * a.j - sin * a.k,
+                t * a.i * a.k + sin * a.j,
+                0.0,
+            ],
+            [
+                t * a.i * a.j + sin * a.k,
+                t * a.j * a.j + cos,
+                t * a.j * a.k - sin * a.i,
+                0.0,
+            ],
+            [
+                t * a.i * a.k - sin * a.j,
+                t * a.j * a.k + sin * a.i,
+                t * a.k * a.k + cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        // rotations are orthogonal
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    // `self` first, then `next`
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: Point) -> Point {
+        self.matrix.mul_point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.mul_vector(v)
+    }
+
+    // normals go through the inverse transpose to stay perpendicular to the surface, the
+    // result isn't a unit vector anymore
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        self.inverse.transpose().mul_vector(n)
+    }
+}
+
+// (i, j, k, w) unit quaternion, only used to interpolate rotations
+#[derive(Copy, Clone, Debug)]
+struct Quaternion([f64; 4]);
+
+impl Quaternion {
+    // from the upper 3x3 of a rotation matrix
+    fn from_rotation(m: &Matrix4) -> Quaternion {
+        let m = &m.m;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        if trace > 0.0 {
+            let s = 0.5 / (trace + 1.0).sqrt();
+            return Quaternion([
+                (m[2][1] - m[1][2]) * s,
+                (m[0][2] - m[2][0]) * s,
+                (m[1][0] - m[0][1]) * s,
+                0.25 / s,
+            ]);
+        }
+
+        // start from the largest diagonal element to keep the square root away from zero
+        let mut i = if m[1][1] > m[0][0] { 1 } else { 0 };
+        if m[2][2] > m[i][i] {
+            i = 2;
+        }
+        let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+        let root = (m[i][i] - m[j][j] - m[k][k] + 1.0).sqrt();
+        let s = 0.5 / root;
+        let mut q = [0.0; 4];
+        q[i] = 0.5 * root;
+        q[j] = (m[j][i] + m[i][j]) * s;
+        q[k] = (m[k][i] + m[i][k]) * s;
+        q[3] = (m[k][j] - m[j][k]) * s;
+        Quaternion(q)
+    }
+
+    fn to_rotation(self) -> Matrix4 {
+        let [x, y, z, w] = self.0;
+        Matrix4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // spherical interpolation along the shorter arc
+    fn slerp(self, other: Quaternion, t: f64) -> Quaternion {
+        let mut cos = (0..4).map(|ix| self.0[ix] * other.0[ix]).sum::<f64>();
+        let mut other = other.0;
+        if cos < 0.0 {
+            cos = -cos;
+            other = other.map(|value| -value);
+        }
+        let (a, b) = if cos > 0.9995 {
+            // nearly the same rotation, a plain blend is fine and avoids dividing by ~0
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+        let q: [f64; 4] = std::array::from_fn(|ix| a * self.0[ix] + b * other[ix]);
+        let length = q.iter().map(|value| value * value).sum::<f64>().sqrt();
+        Quaternion(q.map(|value| value / length))
+    }
+}
+
+// an affine matrix split into translation * rotation * stretch
+#[derive(Copy, Clone, Debug)]
+struct Decomposed {
+    translation: Vec3,
+    rotation: Quaternion,
+    stretch: Matrix4,
+}
+
+impl Decomposed {
+    fn new(matrix: &Matrix4) -> Decomposed {
+        let translation = Vec3::new(matrix.m[0][3], matrix.m[1][3], matrix.m[2][3]);
+        let mut linear = *matrix;
+        for row in 0..3 {
+            linear.m[row][3] = 0.0;
+        }
+
+        // polar decomposition: averaging with the inverse transpose converges to the rotation
+        let mut rotation = linear;
+        for _ in 0..100 {
+            let Some(inverse) = rotation.inverse() else {
+                break;
+            };
+            let inverse_transpose = inverse.transpose();
+            let mut next = rotation;
+            let mut change: f64 = 0.0;
+            for row in 0..3 {
+                for col in 0..3 {
+                    next.m[row][col] = 0.5 * (rotation.m[row][col] + inverse_transpose.m[row][col]);
+                    change = change.max((next.m[row][col] - rotation.m[row][col]).abs());
+                }
+            }
+            rotation = next;
+            if change < 1e-12 {
+                break;
+            }
+        }
+
+        // a mirroring leaves an orthogonal matrix that isn't a rotation, move the flip to the
+        // stretch
+        if rotation.determinant3() < 0.0 {
+            for row in 0..3 {
+                for col in 0..3 {
+                    rotation.m[row][col] = -rotation.m[row][col];
+                }
+            }
+        }
+
+        Decomposed {
+            translation,
+            rotation: Quaternion::from_rotation(&rotation),
+            stretch: rotation.transpose() * linear,
+        }
+    }
+}
+
+/// A transform going from `start` at time 0 to `end` at time 1, and holding still outside of
+/// that. Both ends are split into translation, rotation and stretch, which are interpolated on
+/// their own: blending the matrices directly would shear and shrink a spinning object.
+#[derive(Copy, Clone, Debug)]
+pub struct AnimatedTransform {
+    start: Transform,
+    end: Transform,
+    parts: Option<[Decomposed; 2]>, // `None` when both ends are the same
+}
+
+impl AnimatedTransform {
+    pub fn new(start: Transform, end: Transform) -> AnimatedTransform {
+        let parts = (start.matrix != end.matrix)
+            .then(|| [Decomposed::new(&start.matrix), Decomposed::new(&end.matrix)]);
+        AnimatedTransform { start, end, parts }
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.parts.is_some()
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        let Some([start, end]) = &self.parts else {
+            return self.start;
+        };
+        if time <= 0.0 {
+            return self.start;
+        }
+        if time >= 1.0 {
+            return self.end;
+        }
+
+        let translation = (1.0 - time) * start.translation + time * end.translation;
+        let rotation = start.rotation.slerp(end.rotation, time).to_rotation();
+        let mut stretch = start.stretch;
+        for row in 0..3 {
+            for col in 0..3 {
+                stretch.m[row][col] =
+                    (1.0 - time) * start.stretch.m[row][col] + time * end.stretch.m[row][col];
+            }
+        }
+
+        // a stretch going through zero, like a scale from 1 to -1, has no inverse halfway
+        let mut matrix = rotation * stretch;
+        for row in 0..3 {
+            matrix.m[row][3] = translation[row];
+        }
+        Transform::new(matrix).unwrap_or(self.start)
+    }
+}
+
+impl From<Transform> for AnimatedTransform {
+    fn from(transform: Transform) -> AnimatedTransform {
+        AnimatedTransform::new(transform, transform)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -268,6 +706,130 @@ mod tests {
         assert!(v2.i == 4.0 / 9.0 && v2.j == 4.0 / 9.0 && v2.k == 7.0 / 9.0);
     }
 
+    #[test]
+    fn test_tangent_frame() {
+        for normal in [
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 2.0, -3.0).unit_vector(),
+        ] {
+            let (tangent, bitangent) = normal.tangent_frame();
+
+            assert!((tangent.length() - 1.0).abs() < 1e-12);
+            assert!((bitangent.length() - 1.0).abs() < 1e-12);
+            assert!(tangent.dot(normal).abs() < 1e-12 && bitangent.dot(normal).abs() < 1e-12);
+            assert!((tangent.cross(bitangent) - normal).length() < 1e-12);
+        }
+    }
+
+    fn close(v1: Vec3, v2: Vec3) -> bool {
+        (v1 - v2).length() < 1e-12
+    }
+
+    #[test]
+    fn test_matrix_inverse() {
+        let m1 = Matrix4::new([
+            [2.0, 0.0, 1.0, 3.0],
+            [0.0, 0.0, 4.0, -1.0],
+            [1.0, 3.0, 0.0, 2.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let m2 = m1 * m1.inverse().unwrap();
+
+        for row in 0..4 {
+            for col in 0..4 {
+                let expected = if row == col { 1.0 } else { 0.0 };
+                assert!((m2.m[row][col] - expected).abs() < 1e-12);
+            }
+        }
+        assert!(Matrix4::new([[1.0; 4]; 4]).inverse().is_none());
+    }
+
+    #[test]
+    fn test_transform() {
+        let p1 = Point::new(1.0, 2.0, 3.0);
+        let rotate = Transform::rotation(Vec3::new(0.0, 1.0, 0.0), 90.0);
+        let transform = Transform::scaling(Vec3::new(2.0, 1.0, 1.0))
+            .then(&rotate)
+            .then(&Transform::translation(Vec3::new(0.0, 0.0, 10.0)));
+
+        assert!(close(
+            rotate.point(Point::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.0, 0.0, -1.0)
+        ));
+        assert!(close(transform.point(p1), Point::new(3.0, 2.0, 8.0)));
+        assert!(close(transform.vector(p1), Vec3::new(3.0, 2.0, -2.0)));
+        assert!(close(
+            transform.inverse().point(Point::new(3.0, 2.0, 8.0)),
+            p1
+        ));
+    }
+
+    #[test]
+    fn test_transform_normal() {
+        // the plane x + y = 0 squashed along x becomes 2x + y = 0
+        let scale = Transform::scaling(Vec3::new(0.5, 1.0, 1.0));
+        let normal = scale.normal(Vec3::new(1.0, 1.0, 0.0));
+        let tangent = scale.vector(Vec3::new(1.0, -1.0, 0.0));
+
+        assert!(normal.dot(tangent).abs() < 1e-12);
+        assert!(close(
+            normal.unit_vector(),
+            Vec3::new(2.0, 1.0, 0.0).unit_vector()
+        ));
+    }
+
+    #[test]
+    fn test_animated_transform() {
+        let start = Transform::translation(Vec3::new(1.0, 0.0, 0.0));
+        let end = Transform::scaling(Vec3::new(2.0, 2.0, 2.0))
+            .then(&Transform::rotation(Vec3::new(0.0, 0.0, 1.0), 90.0))
+            .then(&Transform::translation(Vec3::new(3.0, 0.0, 0.0)));
+        let motion = AnimatedTransform::new(start, end);
+        let p1 = Point::new(1.0, 0.0, 0.0);
+
+        assert!(motion.is_animated());
+        assert!(close(motion.at(-1.0).point(p1), Point::new(2.0, 0.0, 0.0)));
+        assert!(close(motion.at(2.0).point(p1), Point::new(3.0, 2.0, 0.0)));
+        // halfway: scaled by 1.5, turned by 45 degrees and moved by 2
+        let halfway = motion.at(0.5);
+        let expected = Point::new(2.0 + 1.5 * 0.5_f64.sqrt(), 1.5 * 0.5_f64.sqrt(), 0.0);
+        assert!((halfway.point(p1) - expected).length() < 1e-9);
+        assert!((halfway.inverse().point(expected) - p1).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_animated_mirror() {
+        // the mirroring can't be a rotation, it has to end up in the stretch
+        let end = Transform::scaling(Vec3::new(-1.0, 1.0, 1.0));
+        let p1 = Point::new(1.0, 2.0, 3.0);
+        let motion = AnimatedTransform::new(end, end.then(&Transform::translation(p1)));
+
+        assert!((motion.at(0.5).point(p1) - Point::new(-0.5, 3.0, 4.5)).length() < 1e-9);
+        assert!(!AnimatedTransform::from(end).is_animated());
+    }
+
+    #[test]
+    fn test_random_cosine_direction() {
+        let random_generator = &mut RandomGenerator::new(0);
+        let samples = 10000;
+        // E[cos] = 2 / 3 under a cos / pi density
+        let mean_cos = (0..samples)
+            .map(|_| Vec3::random_cosine_direction(random_generator))
+            .inspect(|v| assert!((v.length() - 1.0).abs() < 1e-12 && v.k >= 0.0))
+            .map(|v| v.k)
+            .sum::<f64>()
+            / samples as f64;
+
+        assert!((mean_cos - 2.0 / 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_index() {
+        let v1 = Vec3::new(4.0, 5.0, 7.0);
+
+        assert!(v1[0] == 4.0 && v1[1] == 5.0 && v1[2] == 7.0);
+    }
+
     #[test]
     fn test_into() {
         let v1 = Vec3::new(4.0, 4.0, 7.0);